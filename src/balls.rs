@@ -1,59 +1,198 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::ecs::system::EntityCommands;
 use bevy::math::Vec2;
 use bevy::prelude::*;
-use bevy_rapier2d::dynamics::{Ccd, RigidBody};
-use bevy_rapier2d::geometry::Collider;
-use rand::random;
-
-use crate::Modifying;
-
-pub fn despawn_outside_world(
-    mut commands: Commands,
-    query: Query<(Entity, &Transform), Without<Modifying>>,
-    window_query: Query<&Window>,
-) {
-    if let Ok(window) = window_query.get_single() {
-        for (entity, transform) in &mut query.iter() {
-            if transform.translation.y < -window.resolution.height()
-                || transform.translation.x < -window.resolution.width()
-                || transform.translation.x > window.resolution.width()
-                || transform.translation.y > window.resolution.height()
-            {
-                commands.get_entity(entity).map(|mut entity| {
-                    entity.despawn();
-                });
-            }
-        }
+use bevy_rapier2d::dynamics::{Ccd, ExternalForce, RigidBody, Velocity};
+use bevy_rapier2d::geometry::{Collider, ColliderMassProperties, Friction, Restitution};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+
+use crate::textures::ParticleImages;
+
+#[derive(Resource)]
+pub struct BallRng(pub StdRng);
+
+impl Default for BallRng {
+    fn default() -> Self {
+        BallRng(StdRng::from_entropy())
     }
 }
 
 #[derive(Component)]
 pub struct Ball;
 
-pub fn spawn_balls(mut commands: Commands, window_query: Query<&Window>) {
-    let resolution = match window_query.get_single() {
-        Ok(window) => &window.resolution,
-        Err(_) => return,
-    };
-    let width = resolution.width();
-    let height = resolution.height();
+#[derive(EnumIter, Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum ParticleShape {
+    Ball,
+    Capsule,
+    Cuboid,
+    Triangle,
+}
+
+impl ParticleShape {
+    pub fn label(&self) -> &str {
+        match self {
+            ParticleShape::Ball => "Ball",
+            ParticleShape::Capsule => "Capsule",
+            ParticleShape::Cuboid => "Cuboid",
+            ParticleShape::Triangle => "Triangle",
+        }
+    }
+
+    /// Collider for a particle whose half extent is `size`.
+    fn collider(&self, size: f32) -> Collider {
+        match self {
+            ParticleShape::Ball => Collider::ball(size),
+            ParticleShape::Capsule => Collider::capsule_x(size * 0.5, size * 0.5),
+            ParticleShape::Cuboid => Collider::cuboid(size, size),
+            ParticleShape::Triangle => {
+                let [a, b, c] = Self::triangle_vertices().map(|vertex| vertex * size);
+                Collider::triangle(a, b, c)
+            }
+        }
+    }
+
+    /// Extents of the shape's sprite in units of `size`.
+    pub fn extents(&self) -> Vec2 {
+        match self {
+            ParticleShape::Capsule => Vec2::new(2., 1.),
+            _ => Vec2::new(2., 2.),
+        }
+    }
+
+    /// Whether a point in units of `size`, relative to the particle center, is inside the shape.
+    pub fn contains(&self, point: Vec2) -> bool {
+        match self {
+            ParticleShape::Ball => point.length_squared() <= 1.,
+            ParticleShape::Capsule => {
+                let dx = (point.x.abs() - 0.5).max(0.);
+                dx * dx + point.y * point.y <= 0.25
+            }
+            ParticleShape::Cuboid => point.x.abs() <= 1. && point.y.abs() <= 1.,
+            ParticleShape::Triangle => {
+                let [a, b, c] = Self::triangle_vertices();
+                [(a, b), (b, c), (c, a)]
+                    .iter()
+                    .all(|(from, to)| (*to - *from).perp_dot(point - *from) >= 0.)
+            }
+        }
+    }
+
+    /// Counter-clockwise vertices of an equilateral triangle with circumradius 1.
+    fn triangle_vertices() -> [Vec2; 3] {
+        [PI / 2., PI / 2. + TAU / 3., PI / 2. + 2. * TAU / 3.].map(Vec2::from_angle)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum SizeDistribution {
+    #[default]
+    Uniform,
+    /// Centered between min and max, with the range covering three standard deviations each way.
+    Normal,
+    /// Only the min and max sizes, in equal proportion.
+    Bimodal,
+}
+
+/// What an emitter spawns: shapes to pick from, how sizes are distributed and the material.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ParticleSpec {
+    pub shapes: Vec<ParticleShape>,
+    pub min_size: f32,
+    pub max_size: f32,
+    pub size_distribution: SizeDistribution,
+    pub restitution: f32,
+    pub friction: f32,
+    pub density: f32,
+}
+
+impl Default for ParticleSpec {
+    fn default() -> Self {
+        ParticleSpec {
+            shapes: vec![ParticleShape::Ball],
+            min_size: 1.,
+            max_size: 1.,
+            size_distribution: SizeDistribution::Uniform,
+            restitution: 0.,
+            friction: 0.5,
+            density: 1.,
+        }
+    }
+}
+
+impl ParticleSpec {
+    pub fn sample_shape(&self, rng: &mut impl Rng) -> ParticleShape {
+        if self.shapes.is_empty() {
+            ParticleShape::Ball
+        } else {
+            self.shapes[rng.gen_range(0..self.shapes.len())]
+        }
+    }
 
-    let rand_position = Vec2::new(width * (random::<f32>() - 0.5), height * 0.5 + 100.);
-    let half = 1.;
-    let random_color = Color::srgb(random(), random(), random());
+    pub fn sample_size(&self, rng: &mut impl Rng) -> f32 {
+        if self.max_size <= self.min_size {
+            return self.min_size;
+        }
+        match self.size_distribution {
+            SizeDistribution::Uniform => rng.gen_range(self.min_size..self.max_size),
+            SizeDistribution::Normal => {
+                // Box-Muller transform
+                let u1 = 1. - rng.gen::<f32>();
+                let u2 = rng.gen::<f32>();
+                let standard = (-2. * u1.ln()).sqrt() * (TAU * u2).cos();
+                let mean = (self.min_size + self.max_size) / 2.;
+                let std_dev = (self.max_size - self.min_size) / 6.;
+                (mean + standard * std_dev).clamp(self.min_size, self.max_size)
+            }
+            SizeDistribution::Bimodal => {
+                if rng.gen() {
+                    self.min_size
+                } else {
+                    self.max_size
+                }
+            }
+        }
+    }
+}
+
+pub struct Particle {
+    pub shape: ParticleShape,
+    pub size: f32,
+    pub color: Color,
+    pub position: Vec2,
+    pub rotation: f32,
+    pub velocity: Vec2,
+}
 
+pub fn spawn_particle<'a>(
+    commands: &'a mut Commands,
+    images: &ParticleImages,
+    spec: &ParticleSpec,
+    particle: Particle,
+) -> EntityCommands<'a> {
     commands.spawn((
         RigidBody::Dynamic,
-        Collider::ball(half),
+        particle.shape.collider(particle.size),
+        Restitution::coefficient(spec.restitution),
+        Friction::coefficient(spec.friction),
+        ColliderMassProperties::Density(spec.density),
         Ball,
         Ccd::enabled(),
+        Velocity::linear(particle.velocity),
+        ExternalForce::default(),
         Transform {
-            translation: rand_position.extend(0.),
+            translation: particle.position.extend(0.),
+            rotation: Quat::from_rotation_z(particle.rotation),
             ..default()
         },
         Sprite {
-            color: random_color,
-            custom_size: Some(Vec2::new(half * 2., half * 2.)),
+            image: images.get(particle.shape),
+            color: particle.color,
+            custom_size: Some(particle.shape.extents() * particle.size),
             ..default()
         },
-    ));
+    ))
 }
@@ -0,0 +1,74 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+
+use crate::shapes::POLYGON_SIDES;
+use crate::{insert_physics, Modifying, Solid};
+
+/// How a box takes part in the simulation once it has been placed.
+#[derive(EnumIter, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BodyType {
+    /// Never moves.
+    Fixed,
+    /// Only moves when dragged or animated, pushing balls out of the way.
+    #[default]
+    Kinematic,
+    /// Falls and gets pushed around by balls and other solids.
+    Dynamic,
+}
+
+impl BodyType {
+    pub fn label(&self) -> &str {
+        match self {
+            BodyType::Fixed => "Fixed",
+            BodyType::Kinematic => "Kinematic",
+            BodyType::Dynamic => "Dynamic",
+        }
+    }
+
+    pub fn rigid_body(&self) -> RigidBody {
+        match self {
+            BodyType::Fixed => RigidBody::Fixed,
+            BodyType::Kinematic => RigidBody::KinematicVelocityBased,
+            BodyType::Dynamic => RigidBody::Dynamic,
+        }
+    }
+}
+
+/// Settings given to solid bodies created with the box and shape tools.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct BoxDefaults {
+    pub body: BodyType,
+    pub density: f32,
+    pub polygon_sides: u32,
+}
+
+impl Default for BoxDefaults {
+    fn default() -> Self {
+        BoxDefaults {
+            body: BodyType::Kinematic,
+            density: 1.,
+            polygon_sides: *POLYGON_SIDES.start(),
+        }
+    }
+}
+
+/// Re-inserts the physics of boxes whose body type or density was edited in the inspector.
+pub fn refresh_box_bodies(
+    mut commands: Commands,
+    query: Query<
+        (Entity, &Solid, &RigidBody, Option<&ColliderMassProperties>),
+        (Changed<Solid>, Without<Modifying>),
+    >,
+) {
+    for (entity, solid, rigid_body, mass_properties) in &query {
+        let Solid::Box { body, density, .. } = solid else {
+            continue;
+        };
+        let density = ColliderMassProperties::Density(*density);
+        if *rigid_body != body.rigid_body() || mass_properties != Some(&density) {
+            insert_physics(&mut commands.entity(entity), solid);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use bevy::input::mouse::{AccumulatedMouseMotion, AccumulatedMouseScroll, MouseScrollUnit};
+use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+
+use crate::selection::solid_bounds;
+use crate::world::WorldBounds;
+use crate::Solid;
+
+const MIN_ZOOM: f32 = 0.05;
+const MAX_ZOOM: f32 = 20.;
+/// Zoom factor per scroll wheel line.
+const ZOOM_STEP: f32 = 1.1;
+
+/// Asks the camera to frame every solid, or the world bounds when there are none.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct FitSceneEvent;
+
+/// Pans while the middle mouse button, or Space and the left button, are held.
+pub fn is_panning(
+    mouse_input: &ButtonInput<MouseButton>,
+    keyboard_input: &ButtonInput<KeyCode>,
+) -> bool {
+    mouse_input.pressed(MouseButton::Middle)
+        || (keyboard_input.pressed(KeyCode::Space) && mouse_input.pressed(MouseButton::Left))
+}
+
+pub fn pan_camera(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_motion: Res<AccumulatedMouseMotion>,
+    mut camera: Query<(&mut Transform, &OrthographicProjection), With<Camera2d>>,
+) {
+    if !is_panning(&mouse_input, &keyboard_input) || mouse_motion.delta == Vec2::ZERO {
+        return;
+    }
+    let (mut transform, projection) = camera.single_mut();
+    // Screen y points down, world y points up.
+    let delta = Vec2::new(-mouse_motion.delta.x, mouse_motion.delta.y) * projection.scale;
+    transform.translation += delta.extend(0.);
+}
+
+/// Zooms with the scroll wheel, keeping the point under the cursor in place.
+pub fn zoom_camera(
+    mouse_scroll: Res<AccumulatedMouseScroll>,
+    window_query: Query<&Window>,
+    mut camera: Query<(
+        &Camera,
+        &GlobalTransform,
+        &mut Transform,
+        &mut OrthographicProjection,
+    )>,
+    mut egui_contexts: EguiContexts,
+) {
+    if mouse_scroll.delta.y == 0. || egui_contexts.ctx_mut().wants_pointer_input() {
+        return;
+    }
+    let lines = match mouse_scroll.unit {
+        MouseScrollUnit::Line => mouse_scroll.delta.y,
+        MouseScrollUnit::Pixel => mouse_scroll.delta.y / 50.,
+    };
+    let (camera, global_transform, mut transform, mut projection) = camera.single_mut();
+    let old_scale = projection.scale;
+    let new_scale = (old_scale * ZOOM_STEP.powf(-lines)).clamp(MIN_ZOOM, MAX_ZOOM);
+    projection.scale = new_scale;
+
+    let cursor = window_query
+        .single()
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(global_transform, cursor).ok());
+    if let Some(cursor) = cursor {
+        let offset = transform.translation.truncate() - cursor;
+        let translation = cursor + offset * new_scale / old_scale;
+        transform.translation = translation.extend(transform.translation.z);
+    }
+}
+
+pub fn fit_scene(
+    mut event_reader: EventReader<FitSceneEvent>,
+    window_query: Query<&Window>,
+    world_bounds: Res<WorldBounds>,
+    solids: Query<&GlobalTransform, With<Solid>>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<Camera2d>>,
+) {
+    if event_reader.read().count() == 0 {
+        return;
+    }
+    let bounds = solids
+        .iter()
+        .map(solid_bounds)
+        .reduce(|a, b| a.union(b))
+        .unwrap_or_else(|| {
+            Rect::from_center_size(
+                Vec2::ZERO,
+                Vec2::new(world_bounds.width, world_bounds.height),
+            )
+        });
+    let window = window_query.single();
+    let (mut transform, mut projection) = camera.single_mut();
+    let fit = bounds.size() / window.size().max(Vec2::ONE) * 1.1;
+    projection.scale = fit.max_element().clamp(MIN_ZOOM, MAX_ZOOM);
+    transform.translation = bounds.center().extend(transform.translation.z);
+}
@@ -0,0 +1,131 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::balls::Ball;
+use crate::Solid;
+
+pub const COUNTER_COLOR: Color = Color::srgba(1.0, 0.6, 0.0, 0.1);
+/// Seconds of entries the rate is averaged over.
+const RATE_WINDOW: f32 = 5.;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CounterSettings {
+    /// Despawn balls once they are counted.
+    pub consume: bool,
+}
+
+/// Balls counted by a counter since it was created or reset. Inserted along with the counter,
+/// so no balls are missed while it is added.
+#[derive(Component, Debug, Default)]
+pub struct CounterState {
+    pub count: usize,
+    /// When the balls counted within the last `RATE_WINDOW` seconds entered.
+    entries: VecDeque<f32>,
+    /// When counting started, set on the first frame after creation or reset.
+    started: Option<f32>,
+    /// Seconds the rate is averaged over, shorter than `RATE_WINDOW` until that much has passed.
+    window: f32,
+}
+
+impl CounterState {
+    /// Balls per second over the last few seconds.
+    pub fn rate(&self) -> f32 {
+        // At least a second, so the first balls after a reset don't read as a burst.
+        self.entries.len() as f32 / self.window.max(1.)
+    }
+
+    pub fn reset(&mut self) {
+        *self = CounterState::default();
+    }
+}
+
+/// Text showing a counter's count and rate, kept separate from the counter so it isn't scaled
+/// along with it.
+#[derive(Component)]
+pub struct CounterLabel(Entity);
+
+/// Counts balls entering counters, despawning them if the counter consumes them.
+pub fn count_balls(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut counters: Query<(&Solid, &mut CounterState)>,
+    balls: Query<(), With<Ball>>,
+) {
+    let now = time.elapsed_secs();
+    for (_, mut state) in &mut counters {
+        let started = *state.started.get_or_insert(now);
+        state.window = (now - started).min(RATE_WINDOW);
+        while state
+            .entries
+            .front()
+            .is_some_and(|entered| now - entered > RATE_WINDOW)
+        {
+            state.entries.pop_front();
+        }
+    }
+
+    let mut consumed = HashSet::new();
+    for event in collision_events.read() {
+        let CollisionEvent::Started(first, second, _) = *event else {
+            continue;
+        };
+        let (counter, ball) = if balls.contains(second) {
+            (first, second)
+        } else {
+            (second, first)
+        };
+        if !balls.contains(ball) || consumed.contains(&ball) {
+            continue;
+        }
+        let Ok((Solid::Counter(settings), mut state)) = counters.get_mut(counter) else {
+            continue;
+        };
+        state.count += 1;
+        state.entries.push_back(now);
+        if settings.consume {
+            consumed.insert(ball);
+            commands.entity(ball).despawn();
+        }
+    }
+}
+
+/// Keeps a label over every counter and removes the labels of deleted counters.
+pub fn update_counter_labels(
+    mut commands: Commands,
+    counters: Query<(Entity, &GlobalTransform, &CounterState)>,
+    mut labels: Query<(Entity, &CounterLabel, &mut Text2d, &mut Transform)>,
+) {
+    let mut labeled = HashSet::new();
+    for (label_entity, label, mut text, mut transform) in &mut labels {
+        let Ok((_, counter_transform, state)) = counters.get(label.0) else {
+            commands.entity(label_entity).despawn();
+            continue;
+        };
+        labeled.insert(label.0);
+        let content = format!("{}\n{:.1}/s", state.count, state.rate());
+        if text.0 != content {
+            text.0 = content;
+        }
+        let translation = counter_transform.translation() + Vec3::Z * 0.005;
+        if transform.translation != translation {
+            transform.translation = translation;
+        }
+    }
+
+    for (entity, transform, _) in &counters {
+        if !labeled.contains(&entity) {
+            commands.spawn((
+                CounterLabel(entity),
+                Text2d::default(),
+                TextFont::from_font_size(16.),
+                TextLayout::new_with_justify(JustifyText::Center),
+                Transform::from_translation(transform.translation()),
+            ));
+        }
+    }
+}
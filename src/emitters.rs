@@ -0,0 +1,168 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::balls::{spawn_particle, BallRng, Particle, ParticleSpec};
+use crate::textures::ParticleImages;
+use crate::world::WorldBounds;
+use crate::{insert_physics, rotate_force, Hoverable, Modifying, OriginalColor, Solid, ZCounter};
+
+pub const EMITTER_COLOR: Color = Color::srgba(0.0, 1.0, 0.0, 0.1);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EmitterSettings {
+    /// Balls spawned per second.
+    pub rate: f32,
+    /// Launch direction in the emitter's local space.
+    pub direction: Vec2,
+    pub speed: f32,
+    pub particle: ParticleSpec,
+    /// Colors to pick from; random colors are used when empty.
+    pub palette: Vec<Color>,
+    pub max_alive: usize,
+}
+
+impl Default for EmitterSettings {
+    fn default() -> Self {
+        EmitterSettings {
+            rate: 20.,
+            direction: Vec2::new(0., -1.),
+            speed: 0.,
+            particle: ParticleSpec::default(),
+            palette: Vec::new(),
+            max_alive: 1000,
+        }
+    }
+}
+
+/// Fractional number of balls owed by an emitter, carried over between frames.
+#[derive(Component, Default)]
+pub struct EmitterState {
+    pending: f32,
+}
+
+#[derive(Component)]
+pub struct EmittedBy(pub Entity);
+
+/// Height of the default emitter, and how far its center is below the top of the world.
+const DEFAULT_EMITTER_HEIGHT: f32 = 10.;
+
+/// Marks the emitter spawned at startup, which is kept along the top of the world as it resizes.
+#[derive(Component)]
+pub struct DefaultEmitter;
+
+/// Spans the default emitter across the top of the world, just inside its edge.
+fn fit_to_top(transform: &mut Transform, world_bounds: &WorldBounds) {
+    transform.translation.x = 0.;
+    transform.translation.y = world_bounds.half_size().y - DEFAULT_EMITTER_HEIGHT;
+    transform.scale = Vec3::new(world_bounds.width, DEFAULT_EMITTER_HEIGHT, 1.);
+}
+
+/// Replaces the old fixed-rate spawner: a wide emitter along the top of the world.
+pub fn spawn_default_emitter(
+    mut commands: Commands,
+    world_bounds: Res<WorldBounds>,
+    mut z_counter: ResMut<ZCounter>,
+) {
+    let settings = EmitterSettings {
+        rate: 100.,
+        max_alive: 10000,
+        ..default()
+    };
+    let mut transform = Transform::from_xyz(0., 0., z_counter.0);
+    fit_to_top(&mut transform, &world_bounds);
+    let mut entity_commands = commands.spawn((
+        Solid::Emitter(settings.clone()),
+        DefaultEmitter,
+        Hoverable::default(),
+        OriginalColor(EMITTER_COLOR),
+        Sprite {
+            color: EMITTER_COLOR,
+            ..default()
+        },
+        transform,
+    ));
+    insert_physics(&mut entity_commands, &Solid::Emitter(settings));
+    z_counter.0 += 0.01;
+}
+
+/// Keeps the default emitter inside the world when its bounds change.
+pub fn fit_default_emitter(
+    world_bounds: Res<WorldBounds>,
+    mut emitters: Query<&mut Transform, With<DefaultEmitter>>,
+) {
+    if !world_bounds.is_changed() {
+        return;
+    }
+    for mut transform in &mut emitters {
+        fit_to_top(&mut transform, &world_bounds);
+    }
+}
+
+pub fn emit_balls(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut rng: ResMut<BallRng>,
+    images: Res<ParticleImages>,
+    mut emitters: Query<
+        (Entity, &Solid, &GlobalTransform, Option<&mut EmitterState>),
+        Without<Modifying>,
+    >,
+    emitted: Query<&EmittedBy>,
+) {
+    let mut alive: HashMap<Entity, usize> = HashMap::new();
+    for emitted_by in &emitted {
+        *alive.entry(emitted_by.0).or_default() += 1;
+    }
+
+    let rng = &mut rng.0;
+    for (entity, solid, transform, state) in &mut emitters {
+        let Solid::Emitter(settings) = solid else {
+            continue;
+        };
+        let Some(mut state) = state else {
+            commands.entity(entity).insert(EmitterState::default());
+            continue;
+        };
+
+        state.pending += settings.rate * time.delta_secs();
+        let count = state.pending.floor();
+        state.pending -= count;
+
+        let available = settings
+            .max_alive
+            .saturating_sub(alive.get(&entity).copied().unwrap_or_default());
+        let count = (count as usize).min(available);
+
+        let (_, rotation, _) = transform.to_scale_rotation_translation();
+        let z_rotation = rotation.to_euler(EulerRot::ZYX).0;
+        let velocity =
+            rotate_force(settings.direction.normalize_or_zero(), z_rotation) * settings.speed;
+
+        for _ in 0..count {
+            let local = Vec3::new(rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5, 0.);
+            let position = transform.transform_point(local).truncate();
+            let shape = settings.particle.sample_shape(rng);
+            let size = settings.particle.sample_size(rng);
+            let rotation = rng.gen_range(0.0..TAU);
+            let color = if settings.palette.is_empty() {
+                Color::srgb(rng.gen(), rng.gen(), rng.gen())
+            } else {
+                settings.palette[rng.gen_range(0..settings.palette.len())]
+            };
+            let particle = Particle {
+                shape,
+                size,
+                color,
+                position,
+                rotation,
+                velocity,
+            };
+            spawn_particle(&mut commands, &images, &settings.particle, particle)
+                .insert(EmittedBy(entity));
+        }
+    }
+}
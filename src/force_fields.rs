@@ -0,0 +1,315 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::balls::Ball;
+use crate::perlin::Perlin;
+use crate::selection::Selected;
+use crate::{rotate_force, DebugInfo, Solid};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum FieldKind {
+    /// Uniform force along the field's rotated force vector.
+    #[default]
+    Linear,
+    /// Pulls balls towards the center, or pushes them away when `strength` is negative.
+    Radial { strength: f32 },
+    /// Spins balls around the center, counter-clockwise when `strength` is positive.
+    Vortex { strength: f32 },
+    /// Removes the given fraction of a ball's velocity per second.
+    Drag { damping: f32 },
+    /// Like `Linear`, but fading out linearly from the back of the field to the front.
+    Gradient,
+    /// Swirling wind whose direction follows Perlin noise `scale` pixels across, drifting
+    /// `speed` noise cells per second.
+    Turbulence {
+        strength: f32,
+        scale: f32,
+        speed: f32,
+    },
+}
+
+impl FieldKind {
+    pub fn label(&self) -> &str {
+        match self {
+            FieldKind::Linear => "Linear",
+            FieldKind::Radial { .. } => "Radial",
+            FieldKind::Vortex { .. } => "Vortex",
+            FieldKind::Drag { .. } => "Drag",
+            FieldKind::Gradient => "Gradient",
+            FieldKind::Turbulence { .. } => "Turbulence",
+        }
+    }
+}
+
+/// Noise sampled by turbulence fields, shared so every field swirls the same way.
+#[derive(Resource)]
+pub struct FieldNoise(Perlin);
+
+impl Default for FieldNoise {
+    fn default() -> Self {
+        FieldNoise(Perlin::new(0))
+    }
+}
+
+/// Arrows along each axis of the vector grid drawn over turbulence fields.
+const TURBULENCE_GRID: usize = 8;
+
+/// Scales a force field's strength at `local`, a point in the field's unit square, from full
+/// strength at the center down to `1 - falloff` at the edges.
+pub fn falloff_factor(local: Vec2, falloff: f32) -> f32 {
+    let distance = (local.x.abs().max(local.y.abs()) * 2.).min(1.);
+    1. - falloff * distance
+}
+
+/// A field's force vector in its local space.
+fn force_vector(direction: f32, magnitude: f32) -> Vec2 {
+    Vec2::from_angle(direction) * magnitude
+}
+
+/// Force applied by a field at `local` in its unit square, where `offset` is the same point
+/// relative to the field center in world space and `time` is the elapsed time in seconds.
+fn field_force(
+    kind: &FieldKind,
+    force: Vec2,
+    falloff: f32,
+    local: Vec2,
+    offset: Vec2,
+    z_rotation: f32,
+    noise: &FieldNoise,
+    time: f32,
+) -> Vec2 {
+    let factor = falloff_factor(local, falloff);
+    match kind {
+        FieldKind::Linear => rotate_force(force, z_rotation) * factor,
+        FieldKind::Radial { strength } => -offset.normalize_or_zero() * *strength * factor,
+        FieldKind::Vortex { strength } => offset.perp().normalize_or_zero() * *strength * factor,
+        FieldKind::Drag { .. } => Vec2::ZERO,
+        FieldKind::Gradient => {
+            let along = local.dot(force.normalize_or_zero());
+            let gradient = (0.5 - along).clamp(0., 1.);
+            rotate_force(force, z_rotation) * factor * gradient
+        }
+        FieldKind::Turbulence {
+            strength,
+            scale,
+            speed,
+        } => {
+            let point = offset / scale.max(1.) + Vec2::new(time * speed, 0.);
+            Vec2::from_angle(noise.0.fractal(point, 2) * TAU) * *strength * factor
+        }
+    }
+}
+
+pub fn draw_force_fields(
+    query: Query<(&GlobalTransform, &Solid, Has<Selected>)>,
+    noise: Res<FieldNoise>,
+    time: Res<Time>,
+    mut gizmos: Gizmos,
+    mut debug_info: ResMut<DebugInfo>,
+) {
+    let color = Color::srgba(1.0, 1.0, 1.0, 0.5);
+    for (transform, solid, selected) in &query {
+        if let Solid::ForceField {
+            direction,
+            magnitude,
+            falloff,
+            kind,
+        } = solid
+        {
+            let force = force_vector(*direction, *magnitude);
+            let (scale, rotation, translation) = transform.to_scale_rotation_translation();
+            let z_rotation = rotation.to_euler(EulerRot::ZYX).0;
+            let center = translation.truncate();
+            debug_info.transform = transform.compute_transform();
+            debug_info.rotation = rotation;
+            debug_info.rotation_z = z_rotation;
+
+            match kind {
+                FieldKind::Linear | FieldKind::Gradient => {
+                    gizmos.ray_2d(
+                        center,
+                        rotate_force(force, z_rotation) * 100.0,
+                        Color::WHITE,
+                    );
+                }
+                FieldKind::Radial { .. } | FieldKind::Vortex { .. } => {
+                    let radius = scale.x.min(scale.y) / 2.;
+                    gizmos.circle_2d(center, radius, Color::WHITE);
+                }
+                FieldKind::Drag { damping } => {
+                    gizmos.cross_2d(center, 10. * damping.max(0.1), Color::WHITE);
+                }
+                FieldKind::Turbulence { .. } => {}
+            }
+
+            let show_samples = selected || *falloff > 0. || *kind != FieldKind::Linear;
+            if !show_samples {
+                continue;
+            }
+            let steps: Vec<f32> = match kind {
+                FieldKind::Turbulence { .. } => (0..TURBULENCE_GRID)
+                    .map(|i| (i as f32 + 0.5) / TURBULENCE_GRID as f32 - 0.5)
+                    .collect(),
+                _ => vec![-0.35, 0., 0.35],
+            };
+            for &x in &steps {
+                for &y in &steps {
+                    let local = Vec2::new(x, y);
+                    let start = transform.transform_point(local.extend(0.)).truncate();
+                    if let FieldKind::Drag { damping } = kind {
+                        let radius = 5. * damping * falloff_factor(local, *falloff);
+                        gizmos.circle_2d(start, radius, color);
+                        continue;
+                    }
+                    let sample = field_force(
+                        kind,
+                        force,
+                        *falloff,
+                        local,
+                        start - center,
+                        z_rotation,
+                        &noise,
+                        time.elapsed_secs(),
+                    );
+                    gizmos.arrow_2d(start, start + sample * 50.0, color);
+                }
+            }
+        }
+    }
+}
+
+/// Sums the contributions of every force field into each ball's `ExternalForce` in place, so
+/// overlapping fields compose. Each field only visits the balls its collider intersects.
+pub fn apply_force_field(
+    rapier_context: ReadDefaultRapierContext,
+    query: Query<(&GlobalTransform, &Solid, &Collider)>,
+    mut balls_query: Query<
+        (&GlobalTransform, &mut ExternalForce, Option<&mut Velocity>),
+        With<Ball>,
+    >,
+    noise: Res<FieldNoise>,
+    time: Res<Time>,
+) {
+    for (_, mut external_force, _) in &mut balls_query {
+        if external_force.force != Vec2::ZERO {
+            external_force.force = Vec2::ZERO;
+        }
+    }
+
+    let filter = QueryFilter::only_dynamic().exclude_sensors();
+    for (transform, solid, collider) in &query {
+        if let Solid::ForceField {
+            direction,
+            magnitude,
+            falloff,
+            kind,
+        } = solid
+        {
+            let force = force_vector(*direction, *magnitude);
+            let (_, rotation, translation) = transform.to_scale_rotation_translation();
+            let z_rotation = rotation.to_euler(EulerRot::ZYX).0;
+            let center = translation.truncate();
+            let inverse = transform.compute_matrix().inverse();
+            rapier_context.intersections_with_shape(
+                center,
+                z_rotation,
+                collider,
+                filter,
+                |entity| {
+                    let Ok((ball_transform, mut external_force, velocity)) =
+                        balls_query.get_mut(entity)
+                    else {
+                        return true;
+                    };
+                    let position = ball_transform.translation();
+                    let local = inverse.transform_point3(position).truncate();
+                    if let FieldKind::Drag { damping } = kind {
+                        if let Some(mut velocity) = velocity {
+                            let factor = falloff_factor(local, *falloff);
+                            let keep = (1. - damping * factor * time.delta_secs()).max(0.);
+                            velocity.linvel *= keep;
+                            velocity.angvel *= keep;
+                        }
+                        return true;
+                    }
+                    external_force.force += field_force(
+                        kind,
+                        force,
+                        *falloff,
+                        local,
+                        position.truncate() - center,
+                        z_rotation,
+                        &noise,
+                        time.elapsed_secs(),
+                    );
+                    true
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use bevy::app::ScheduleRunnerPlugin;
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    fn linear_field(direction: f32, magnitude: f32) -> impl Bundle {
+        (
+            Solid::ForceField {
+                direction,
+                magnitude,
+                falloff: 0.,
+                kind: FieldKind::Linear,
+            },
+            Transform::from_scale(Vec3::new(200., 200., 1.)),
+            Collider::cuboid(0.5, 0.5),
+            RigidBody::KinematicVelocityBased,
+            Sensor,
+        )
+    }
+
+    #[test]
+    fn overlapping_linear_fields_add_up() {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins.build().disable::<ScheduleRunnerPlugin>(),
+            TransformPlugin,
+            HierarchyPlugin,
+            AssetPlugin::default(),
+        ))
+        .init_asset::<Mesh>()
+        .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.))
+        .init_resource::<FieldNoise>();
+        app.finish();
+        app.cleanup();
+
+        app.world_mut().spawn(linear_field(0., 3.));
+        app.world_mut().spawn(linear_field(FRAC_PI_2, 4.));
+        let ball = app
+            .world_mut()
+            .spawn((
+                Ball,
+                Transform::default(),
+                RigidBody::Dynamic,
+                Collider::ball(5.),
+                GravityScale(0.),
+                ExternalForce::default(),
+            ))
+            .id();
+        // Let rapier pick up the colliders before querying for intersections.
+        app.update();
+        app.update();
+
+        app.world_mut().run_system_once(apply_force_field).unwrap();
+        let force = app.world().get::<ExternalForce>(ball).unwrap().force;
+        assert!(force.distance(Vec2::new(3., 4.)) < 1e-4, "got {force}");
+    }
+}
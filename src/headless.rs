@@ -0,0 +1,161 @@
+use std::time::Duration;
+
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy_rapier2d::plugin::TimestepMode;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::balls::{Ball, BallRng};
+use crate::scene::{SceneEvent, SceneFile};
+use crate::world::WorldBounds;
+use crate::SimulationPlugin;
+
+const USAGE: &str = "Usage: bevy_physics_playground --headless [--steps N] [--seed N] \
+[--scene PATH] [--width W] [--height H] [--dt SECONDS]";
+
+/// Settings for running the simulation without a window, parsed from the command line.
+#[derive(Debug, Clone)]
+pub struct HeadlessConfig {
+    pub steps: u32,
+    pub seed: u64,
+    pub scene: Option<String>,
+    pub world_bounds: WorldBounds,
+    pub dt: f32,
+}
+
+impl Default for HeadlessConfig {
+    fn default() -> Self {
+        HeadlessConfig {
+            steps: 600,
+            seed: 0,
+            scene: None,
+            world_bounds: WorldBounds::default(),
+            dt: 1. / 60.,
+        }
+    }
+}
+
+impl HeadlessConfig {
+    /// Returns `Ok(None)` when `--headless` is not among the arguments. Unknown arguments are
+    /// only rejected in headless mode, since the windowed app may be handed arguments by the
+    /// platform, but headless flags without `--headless` are reported.
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut config = HeadlessConfig::default();
+        let mut headless = false;
+        let mut sized = false;
+        let mut headless_flag = None;
+        let mut unknown = None;
+
+        while let Some(arg) = args.next() {
+            if arg == "--headless" {
+                headless = true;
+                continue;
+            }
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for {arg}\n{USAGE}"))
+            };
+            let invalid = |value: &str| format!("Invalid value for {arg}: {value}\n{USAGE}");
+            match arg.as_str() {
+                "--steps" => {
+                    let value = value()?;
+                    config.steps = value.parse().map_err(|_| invalid(&value))?;
+                }
+                "--seed" => {
+                    let value = value()?;
+                    config.seed = value.parse().map_err(|_| invalid(&value))?;
+                }
+                "--scene" => config.scene = Some(value()?),
+                "--width" => {
+                    sized = true;
+                    let value = value()?;
+                    config.world_bounds.width = value.parse().map_err(|_| invalid(&value))?;
+                }
+                "--height" => {
+                    sized = true;
+                    let value = value()?;
+                    config.world_bounds.height = value.parse().map_err(|_| invalid(&value))?;
+                }
+                "--dt" => {
+                    let value = value()?;
+                    config.dt = value.parse().map_err(|_| invalid(&value))?;
+                }
+                _ => {
+                    unknown.get_or_insert(arg);
+                    continue;
+                }
+            }
+            headless_flag.get_or_insert(arg);
+        }
+
+        if !headless {
+            return match headless_flag {
+                Some(flag) => Err(format!("{flag} requires --headless\n{USAGE}")),
+                None => Ok(None),
+            };
+        }
+        if let Some(arg) = unknown {
+            return Err(format!("Unknown argument: {arg}\n{USAGE}"));
+        }
+        // The scene's own bounds would silently replace the requested size once it loads.
+        if sized && config.scene.is_some() {
+            return Err(format!(
+                "--width and --height can't be combined with --scene, which sets the world size\n{USAGE}"
+            ));
+        }
+
+        Ok(Some(config))
+    }
+}
+
+/// Runs the simulation for a fixed number of steps of `dt` seconds each, then prints the ball
+/// count and positions. Both the virtual clock and rapier advance by exactly `dt` per step, so
+/// a given config always produces the same output.
+pub fn run(config: HeadlessConfig) {
+    let dt = Duration::from_secs_f32(config.dt);
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins.build().disable::<ScheduleRunnerPlugin>(),
+        TransformPlugin,
+        HierarchyPlugin,
+        AssetPlugin::default(),
+    ))
+    .init_asset::<Mesh>()
+    .init_asset::<ColorMaterial>()
+    .init_asset::<Image>()
+    .add_plugins(SimulationPlugin)
+    .insert_resource(TimeUpdateStrategy::ManualDuration(dt))
+    .insert_resource(TimestepMode::Fixed {
+        dt: config.dt,
+        substeps: 1,
+    })
+    .insert_resource(config.world_bounds)
+    .insert_resource(BallRng(StdRng::seed_from_u64(config.seed)));
+
+    if let Some(scene) = &config.scene {
+        app.insert_resource(SceneFile(scene.clone()));
+        app.world_mut().send_event(SceneEvent::Load);
+    }
+
+    app.finish();
+    app.cleanup();
+
+    for _ in 0..config.steps {
+        app.update();
+    }
+
+    let world = app.world_mut();
+    let mut query = world.query_filtered::<&Transform, With<Ball>>();
+    let positions: Vec<Vec2> = query
+        .iter(world)
+        .map(|transform| transform.translation.truncate())
+        .collect();
+
+    println!("steps: {}", config.steps);
+    println!("balls: {}", positions.len());
+    for position in positions {
+        println!("{:.3} {:.3}", position.x, position.y);
+    }
+}
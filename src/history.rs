@@ -0,0 +1,265 @@
+use bevy::prelude::*;
+
+use crate::joints::{despawn_joint, Joint, WorldAnchor};
+use crate::scene::{SceneJoint, SceneJointBody, SceneSolid};
+use crate::textures::Meshes;
+use crate::{Mode, Solid};
+
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub enum HistoryEvent {
+    Undo,
+    Redo,
+}
+
+#[derive(Debug, Clone)]
+pub enum HistoryEntry {
+    Created {
+        entity: Entity,
+        solid: SceneSolid,
+    },
+    Deleted {
+        entity: Entity,
+        solid: SceneSolid,
+    },
+    Transformed {
+        entity: Entity,
+        before: Transform,
+        after: Transform,
+    },
+    /// A joint created or deleted, either on its own or along with the solids it is attached to in
+    /// the same step.
+    Joint {
+        joint: SceneJoint<Entity>,
+        created: bool,
+    },
+}
+
+impl HistoryEntry {
+    pub fn label(&self) -> String {
+        match self {
+            HistoryEntry::Created { solid, .. } => format!("Create {}", solid.solid.label()),
+            HistoryEntry::Deleted { solid, .. } => format!("Delete {}", solid.solid.label()),
+            HistoryEntry::Transformed { .. } => "Transform".to_string(),
+            HistoryEntry::Joint { created: true, .. } => "Create Joint".to_string(),
+            HistoryEntry::Joint { created: false, .. } => "Delete Joint".to_string(),
+        }
+    }
+
+    fn remap(&mut self, from: Entity, to: Entity) {
+        let entities = match self {
+            HistoryEntry::Created { entity, .. } => vec![entity],
+            HistoryEntry::Deleted { entity, .. } => vec![entity],
+            HistoryEntry::Transformed { entity, .. } => vec![entity],
+            HistoryEntry::Joint { joint, .. } => joint
+                .bodies
+                .iter_mut()
+                .filter_map(|body| match body {
+                    SceneJointBody::Solid(entity) => Some(entity),
+                    SceneJointBody::World(_) => None,
+                })
+                .collect(),
+        };
+        for entity in entities {
+            if *entity == from {
+                *entity = to;
+            }
+        }
+    }
+}
+
+/// Entries recorded by a single command, undone and redone together.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryStep(Vec<HistoryEntry>);
+
+impl HistoryStep {
+    pub fn push(&mut self, entry: HistoryEntry) {
+        self.0.push(entry);
+    }
+
+    pub fn label(&self) -> String {
+        let solids = self
+            .0
+            .iter()
+            .filter(|entry| !matches!(entry, HistoryEntry::Joint { .. }))
+            .count();
+        match self.0.first() {
+            Some(entry) if solids > 1 => format!("{} ({solids} solids)", entry.label()),
+            Some(entry) => entry.label(),
+            None => String::new(),
+        }
+    }
+
+    fn remap(&mut self, from: Entity, to: Entity) {
+        for entry in &mut self.0 {
+            entry.remap(from, to);
+        }
+    }
+}
+
+impl From<HistoryEntry> for HistoryStep {
+    fn from(entry: HistoryEntry) -> Self {
+        HistoryStep(vec![entry])
+    }
+}
+
+/// Undo and redo stacks of completed commands. Entities despawned by an undo are respawned
+/// on redo, so entries referring to them are remapped to the new entity.
+#[derive(Resource, Debug, Default)]
+pub struct History {
+    undo: Vec<HistoryStep>,
+    redo: Vec<HistoryStep>,
+}
+
+impl History {
+    /// Records a step, ignoring empty ones.
+    pub fn push(&mut self, step: impl Into<HistoryStep>) {
+        let step = step.into();
+        if step.0.is_empty() {
+            return;
+        }
+        self.undo.push(step);
+        self.redo.clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    pub fn undo_steps(&self) -> &[HistoryStep] {
+        &self.undo
+    }
+
+    pub fn redo_steps(&self) -> &[HistoryStep] {
+        &self.redo
+    }
+
+    fn remap(&mut self, from: Entity, to: Entity) {
+        for step in self.undo.iter_mut().chain(self.redo.iter_mut()) {
+            step.remap(from, to);
+        }
+    }
+
+    /// Keeps the solids that undo and redo would respawn pointing at the same materials after
+    /// the one at `removed` is taken out of the library.
+    pub fn remove_material(&mut self, removed: usize) {
+        for step in self.undo.iter_mut().chain(self.redo.iter_mut()) {
+            for entry in &mut step.0 {
+                if let HistoryEntry::Created { solid, .. } | HistoryEntry::Deleted { solid, .. } =
+                    entry
+                {
+                    solid.solid.remove_material(removed);
+                }
+            }
+        }
+    }
+}
+
+/// Despawns `entity` when `despawn` is set, otherwise respawns `solid` and returns the new
+/// entity.
+fn toggle_existence(
+    commands: &mut Commands,
+    meshes: &Meshes,
+    materials: &mut Assets<ColorMaterial>,
+    entity: Entity,
+    solid: &SceneSolid,
+    despawn: bool,
+) -> Option<Entity> {
+    if despawn {
+        if let Some(mut entity_commands) = commands.get_entity(entity) {
+            entity_commands.despawn();
+        }
+        None
+    } else {
+        Some(solid.spawn(commands, meshes, materials))
+    }
+}
+
+pub fn handle_history_events(
+    mut event_reader: EventReader<HistoryEvent>,
+    mut commands: Commands,
+    mode: Res<Mode>,
+    mut history: ResMut<History>,
+    meshes: Res<Meshes>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut query: Query<&mut Transform, (With<Solid>, Without<WorldAnchor>)>,
+    joints: Query<(Entity, &Joint)>,
+    anchors: Query<&Transform, With<WorldAnchor>>,
+) {
+    for event in event_reader.read() {
+        if *mode != Mode::Default {
+            continue;
+        }
+        let undo = *event == HistoryEvent::Undo;
+        let popped = if undo {
+            history.undo.pop()
+        } else {
+            history.redo.pop()
+        };
+        let Some(mut step) = popped else {
+            continue;
+        };
+
+        let mut remapped = Vec::new();
+        let mut apply = |entry: &mut HistoryEntry| {
+            let respawned = match entry {
+                HistoryEntry::Created { entity, solid } => {
+                    toggle_existence(&mut commands, &meshes, &mut materials, *entity, solid, undo)
+                        .map(|new_entity| (*entity, new_entity))
+                }
+                HistoryEntry::Deleted { entity, solid } => toggle_existence(
+                    &mut commands,
+                    &meshes,
+                    &mut materials,
+                    *entity,
+                    solid,
+                    !undo,
+                )
+                .map(|new_entity| (*entity, new_entity)),
+                HistoryEntry::Transformed {
+                    entity,
+                    before,
+                    after,
+                } => {
+                    if let Ok(mut transform) = query.get_mut(*entity) {
+                        *transform = if undo { *before } else { *after };
+                    }
+                    None
+                }
+                // Respawned below, once the solids they are attached to have been remapped.
+                HistoryEntry::Joint { .. } => None,
+            };
+            remapped.extend(respawned);
+        };
+        if undo {
+            step.0.iter_mut().rev().for_each(&mut apply);
+        } else {
+            step.0.iter_mut().for_each(&mut apply);
+        }
+
+        for (from, to) in remapped {
+            step.remap(from, to);
+            history.remap(from, to);
+        }
+        for entry in &step.0 {
+            let HistoryEntry::Joint { joint, created } = entry else {
+                continue;
+            };
+            if *created != undo {
+                joint.spawn(&mut commands);
+                continue;
+            }
+            let live = joints
+                .iter()
+                .find(|(_, live)| SceneJoint::from_joint(live, &anchors) == *joint);
+            if let Some((entity, live)) = live {
+                despawn_joint(&mut commands, entity, live, &anchors);
+            }
+        }
+        if undo {
+            history.redo.push(step);
+        } else {
+            history.undo.push(step);
+        }
+    }
+}
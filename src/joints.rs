@@ -0,0 +1,402 @@
+use std::f32::consts::FRAC_PI_4;
+
+use bevy::ecs::query::QueryData;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+
+use crate::history::{History, HistoryEntry};
+use crate::scene::{SceneJoint, SceneJointBody};
+use crate::{Hoverable, Mode, Mouse, Solid};
+
+/// Damping factor of velocity motors.
+const MOTOR_FACTOR: f32 = 1.;
+
+#[derive(EnumIter, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum JointType {
+    #[default]
+    Revolute,
+    Prismatic,
+    Fixed,
+    Rope,
+    Spring,
+}
+
+impl JointType {
+    pub fn label(&self) -> &str {
+        match self {
+            JointType::Revolute => "Revolute",
+            JointType::Prismatic => "Prismatic",
+            JointType::Fixed => "Fixed",
+            JointType::Rope => "Rope",
+            JointType::Spring => "Spring",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Limits {
+    pub enabled: bool,
+    pub min: f32,
+    pub max: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Motor {
+    pub enabled: bool,
+    pub target_velocity: f32,
+    pub max_force: f32,
+}
+
+impl Default for Motor {
+    fn default() -> Self {
+        Motor {
+            enabled: false,
+            target_velocity: 1.,
+            max_force: 1e8,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum JointKind {
+    /// Limits are angles in radians, the motor velocity is in radians per second.
+    Revolute {
+        limits: Limits,
+        motor: Motor,
+    },
+    /// Slides along `axes`, given in the local space of each body.
+    Prismatic {
+        axes: [Vec2; 2],
+        limits: Limits,
+        motor: Motor,
+    },
+    /// Keeps the bodies at the angle they had when the joint was created.
+    Fixed {
+        relative_angle: f32,
+    },
+    Rope {
+        length: f32,
+    },
+    Spring {
+        rest_length: f32,
+        stiffness: f32,
+        damping: f32,
+    },
+}
+
+impl JointKind {
+    pub fn joint_type(&self) -> JointType {
+        match self {
+            JointKind::Revolute { .. } => JointType::Revolute,
+            JointKind::Prismatic { .. } => JointType::Prismatic,
+            JointKind::Fixed { .. } => JointType::Fixed,
+            JointKind::Rope { .. } => JointType::Rope,
+            JointKind::Spring { .. } => JointType::Spring,
+        }
+    }
+}
+
+/// A joint between two bodies, kept on a child entity of the second body. Either body may be
+/// a `WorldAnchor`.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct Joint {
+    pub kind: JointKind,
+    pub bodies: [Entity; 2],
+    /// Anchor points in the local space of each body, ignoring the body's scale.
+    pub anchors: [Vec2; 2],
+}
+
+impl Joint {
+    fn typed_joint(&self) -> TypedJoint {
+        let [anchor1, anchor2] = self.anchors;
+        match self.kind {
+            JointKind::Revolute { limits, motor } => {
+                let mut builder = RevoluteJointBuilder::new()
+                    .local_anchor1(anchor1)
+                    .local_anchor2(anchor2);
+                if limits.enabled {
+                    builder = builder.limits([limits.min, limits.max]);
+                }
+                if motor.enabled {
+                    builder = builder
+                        .motor_velocity(motor.target_velocity, MOTOR_FACTOR)
+                        .motor_max_force(motor.max_force);
+                }
+                builder.into()
+            }
+            JointKind::Prismatic {
+                axes,
+                limits,
+                motor,
+            } => {
+                let mut builder = PrismaticJointBuilder::new(axes[0])
+                    .local_axis2(axes[1])
+                    .local_anchor1(anchor1)
+                    .local_anchor2(anchor2);
+                if limits.enabled {
+                    builder = builder.limits([limits.min, limits.max]);
+                }
+                if motor.enabled {
+                    builder = builder
+                        .motor_velocity(motor.target_velocity, MOTOR_FACTOR)
+                        .motor_max_force(motor.max_force);
+                }
+                builder.into()
+            }
+            JointKind::Fixed { relative_angle } => FixedJointBuilder::new()
+                .local_basis2(relative_angle)
+                .local_anchor1(anchor1)
+                .local_anchor2(anchor2)
+                .into(),
+            JointKind::Rope { length } => RopeJointBuilder::new(length)
+                .local_anchor1(anchor1)
+                .local_anchor2(anchor2)
+                .into(),
+            JointKind::Spring {
+                rest_length,
+                stiffness,
+                damping,
+            } => SpringJointBuilder::new(rest_length, stiffness, damping)
+                .local_anchor1(anchor1)
+                .local_anchor2(anchor2)
+                .into(),
+        }
+    }
+}
+
+/// Fixed body spawned where a joint is attached to the world rather than to a solid.
+#[derive(Component)]
+pub struct WorldAnchor;
+
+pub fn spawn_world_anchor(commands: &mut Commands, point: Vec2) -> Entity {
+    commands
+        .spawn((
+            WorldAnchor,
+            RigidBody::Fixed,
+            Transform::from_translation(point.extend(0.)),
+        ))
+        .id()
+}
+
+/// Spawns `joint` as a child of its second body.
+pub fn spawn_joint(commands: &mut Commands, joint: Joint) -> Entity {
+    let entity = commands.spawn(joint).id();
+    commands.entity(joint.bodies[1]).add_child(entity);
+    entity
+}
+
+/// A point picked with the joint tool, on a solid or on the world when `body` is `None`.
+#[derive(Debug, Clone, Copy)]
+pub struct JointPick {
+    body: Option<Entity>,
+    point: Vec2,
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct JointTool {
+    pub joint_type: JointType,
+    first: Option<JointPick>,
+}
+
+fn local_point(transform: &GlobalTransform, point: Vec2) -> Vec2 {
+    let (_, rotation, translation) = transform.to_scale_rotation_translation();
+    (rotation.inverse() * (point.extend(0.) - translation)).truncate()
+}
+
+fn world_point(transform: &GlobalTransform, local: Vec2) -> Vec2 {
+    let (_, rotation, translation) = transform.to_scale_rotation_translation();
+    (translation + rotation * local.extend(0.)).truncate()
+}
+
+fn z_angle(transform: &GlobalTransform) -> f32 {
+    let (_, rotation, _) = transform.to_scale_rotation_translation();
+    rotation.to_euler(EulerRot::ZYX).0
+}
+
+/// Picks two points, each on a solid or on the world, and joins them with the chosen joint.
+pub fn handle_joint_tool(
+    mut commands: Commands,
+    mode: Res<Mode>,
+    mouse: Res<Mouse>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    mut joint_tool: ResMut<JointTool>,
+    mut history: ResMut<History>,
+    hovered: Query<(Entity, &Hoverable, &Solid)>,
+    transforms: Query<&GlobalTransform>,
+    mut gizmos: Gizmos,
+    mut egui_contexts: EguiContexts,
+) {
+    if *mode != Mode::Joint {
+        joint_tool.first = None;
+        return;
+    }
+    let ctx = egui_contexts.ctx_mut();
+    if let Some(first) = joint_tool.first {
+        gizmos.line_2d(first.point, mouse.position, Color::WHITE);
+    }
+    if ctx.is_pointer_over_area() {
+        return;
+    }
+    ctx.set_cursor_icon(egui::CursorIcon::Crosshair);
+    if !mouse_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let pick = JointPick {
+        body: hovered
+            .iter()
+            .find(|(_, hoverable, solid)| {
+                hoverable.position.is_some() && matches!(solid, Solid::Box { .. })
+            })
+            .map(|(entity, ..)| entity),
+        point: mouse.position,
+    };
+    let Some(first) = joint_tool.first.take() else {
+        joint_tool.first = Some(pick);
+        return;
+    };
+    if first.body == pick.body {
+        joint_tool.first = Some(first);
+        return;
+    }
+
+    let mut attach = |pick: JointPick| match pick.body {
+        Some(body) => {
+            let transform = transforms.get(body).copied().unwrap_or_default();
+            (
+                body,
+                local_point(&transform, pick.point),
+                z_angle(&transform),
+            )
+        }
+        None => (
+            spawn_world_anchor(&mut commands, pick.point),
+            Vec2::ZERO,
+            0.,
+        ),
+    };
+    let (body1, anchor1, angle1) = attach(first);
+    let (body2, anchor2, angle2) = attach(pick);
+
+    let distance = first.point.distance(pick.point);
+    let axis = (pick.point - first.point).normalize_or(Vec2::X);
+    let kind = match joint_tool.joint_type {
+        JointType::Revolute => JointKind::Revolute {
+            limits: Limits {
+                enabled: false,
+                min: -FRAC_PI_4,
+                max: FRAC_PI_4,
+            },
+            motor: Motor::default(),
+        },
+        JointType::Prismatic => JointKind::Prismatic {
+            axes: [
+                Vec2::from_angle(-angle1).rotate(axis),
+                Vec2::from_angle(-angle2).rotate(axis),
+            ],
+            limits: Limits {
+                enabled: false,
+                min: -100.,
+                max: 100.,
+            },
+            motor: Motor::default(),
+        },
+        JointType::Fixed => JointKind::Fixed {
+            relative_angle: angle1 - angle2,
+        },
+        JointType::Rope => JointKind::Rope { length: distance },
+        JointType::Spring => JointKind::Spring {
+            rest_length: distance,
+            stiffness: 20000.,
+            damping: 500.,
+        },
+    };
+    spawn_joint(
+        &mut commands,
+        Joint {
+            kind,
+            bodies: [body1, body2],
+            anchors: [anchor1, anchor2],
+        },
+    );
+    let scene_body = |pick: JointPick| match pick.body {
+        Some(body) => SceneJointBody::Solid(body),
+        None => SceneJointBody::World(pick.point),
+    };
+    history.push(HistoryEntry::Joint {
+        joint: SceneJoint {
+            kind,
+            bodies: [scene_body(first), scene_body(pick)],
+            anchors: [anchor1, anchor2],
+        },
+        created: true,
+    });
+}
+
+/// Rebuilds the rapier joint whenever its settings change.
+pub fn sync_joints(mut commands: Commands, joints: Query<(Entity, &Joint), Changed<Joint>>) {
+    for (entity, joint) in &joints {
+        commands
+            .entity(entity)
+            .insert(ImpulseJoint::new(joint.bodies[0], joint.typed_joint()));
+    }
+}
+
+/// Despawns a joint along with the world anchors it used.
+pub fn despawn_joint<D: QueryData>(
+    commands: &mut Commands,
+    entity: Entity,
+    joint: &Joint,
+    anchors: &Query<D, With<WorldAnchor>>,
+) {
+    commands.entity(entity).despawn_recursive();
+    for body in joint.bodies {
+        if anchors.contains(body) {
+            commands.entity(body).despawn_recursive();
+        }
+    }
+}
+
+/// Removes joints whose bodies are gone, along with the world anchors they used.
+pub fn remove_broken_joints(
+    mut commands: Commands,
+    joints: Query<(Entity, &Joint)>,
+    bodies: Query<(), With<RigidBody>>,
+    anchors: Query<(), With<WorldAnchor>>,
+) {
+    for (entity, joint) in &joints {
+        if !joint.bodies.iter().all(|body| bodies.contains(*body)) {
+            despawn_joint(&mut commands, entity, joint, &anchors);
+        }
+    }
+}
+
+pub fn draw_joints(joints: Query<&Joint>, transforms: Query<&GlobalTransform>, mut gizmos: Gizmos) {
+    let color = Color::srgb(1.0, 0.5, 0.0);
+    for joint in &joints {
+        let Ok([transform1, transform2]) = transforms.get_many(joint.bodies) else {
+            continue;
+        };
+        let start = world_point(transform1, joint.anchors[0]);
+        let end = world_point(transform2, joint.anchors[1]);
+        gizmos.circle_2d(start, 4., color);
+        gizmos.circle_2d(end, 4., color);
+        match joint.kind {
+            JointKind::Revolute { .. } => {
+                gizmos.circle_2d(end, 8., color);
+            }
+            JointKind::Prismatic { axes, .. } => {
+                let axis = Vec2::from_angle(z_angle(transform1)).rotate(axes[0]);
+                gizmos.line_2d(start - axis * 50., start + axis * 50., color);
+            }
+            JointKind::Fixed { .. } => {
+                gizmos.rect_2d(end, Vec2::splat(8.), color);
+            }
+            JointKind::Rope { .. } | JointKind::Spring { .. } => {}
+        }
+        gizmos.line_2d(start, end, color);
+    }
+}
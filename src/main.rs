@@ -1,26 +1,69 @@
 #![allow(unused_parens)]
 
-use std::time::Duration;
+use std::f32::consts::FRAC_PI_2;
 
+use bevy::diagnostic::FrameTimeDiagnosticsPlugin;
+use bevy::ecs::system::EntityCommands;
+use bevy::prelude::*;
 use bevy::utils::HashSet;
-use bevy::{prelude::*, time::common_conditions::on_timer};
 use bevy_egui::{egui, EguiContexts};
 use bevy_inspector_egui::quick::ResourceInspectorPlugin;
 use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
-use textures::Meshes;
+use textures::{MeshVariant, Meshes};
 use Command::Created;
 use Command::Scaled;
 
-use crate::balls::Ball;
-use crate::Command::{Move, Rotate};
+use crate::balls::BallRng;
+use crate::bodies::{BodyType, BoxDefaults};
+use crate::camera::{is_panning, FitSceneEvent};
+use crate::counters::{CounterSettings, CounterState, COUNTER_COLOR};
+use crate::emitters::{EmitterSettings, EMITTER_COLOR};
+use crate::force_fields::{FieldKind, FieldNoise};
+use crate::headless::HeadlessConfig;
+use crate::history::{History, HistoryEntry, HistoryEvent, HistoryStep};
+use crate::joints::{Joint, JointTool, WorldAnchor};
+use crate::materials::{MaterialImages, MaterialLibrary};
+use crate::motion::Motion;
+use crate::polylines::{DrawnPath, POLYLINE_COLOR};
+use crate::scene::{joints_between, SceneEvent, SceneJoint, SceneSolid};
+use crate::selection::{shift_pressed, solid_bounds, RubberBand, Selected, SelectionEvent};
+use crate::shapes::SolidShape;
+use crate::snapping::{SnapTargets, Snapping};
+use crate::stats::{SimulationStats, StepTimer};
+use crate::terrain::{TerrainHeights, TerrainSettings, TERRAIN_COLOR};
+use crate::time_control::TimeControl;
+use crate::world::WorldBounds;
+use crate::Command::{
+    CopyToClipboard, Delete, Duplicate, Move, PasteFromClipboard, Resize, Rotate,
+};
 
 mod balls;
+mod bodies;
+mod camera;
+mod counters;
+mod emitters;
+mod force_fields;
+mod headless;
+mod history;
+mod joints;
+mod materials;
+mod motion;
 mod perlin;
+mod polylines;
+mod scene;
+mod selection;
+mod shapes;
+mod snapping;
+mod stats;
+mod terrain;
 mod textures;
+mod time_control;
 mod ui;
+mod world;
 
 struct MainPlugin;
 
@@ -34,46 +77,158 @@ impl Plugin for MainPlugin {
             .register_type::<DebugInfo>()
             .insert_resource(Mode::Default)
             .insert_resource(ZCounter::default())
-            .insert_resource(Mouse::default());
+            .insert_resource(Mouse::default())
+            .init_resource::<scene::SceneFile>()
+            .init_resource::<History>()
+            .init_resource::<BoxDefaults>()
+            .init_resource::<MaterialLibrary>();
+    }
+}
+
+/// Everything needed to run the simulation, shared by the windowed and headless apps.
+struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.))
+            .add_plugins(MainPlugin)
+            .init_resource::<WorldBounds>()
+            .init_resource::<BallRng>()
+            .init_resource::<FieldNoise>()
+            .add_systems(Startup, textures::generate_textures)
+            .add_systems(Startup, emitters::spawn_default_emitter)
+            .add_event::<SceneEvent>()
+            .add_systems(Update, emitters::emit_balls)
+            .add_systems(Update, counters::count_balls)
+            .add_systems(Update, world::sync_world_walls)
+            .add_systems(Update, emitters::fit_default_emitter)
+            .add_systems(PostUpdate, world::apply_world_edges)
+            .add_systems(Update, force_fields::apply_force_field)
+            .add_systems(Update, scene::handle_scene_events)
+            .add_systems(Update, bodies::refresh_box_bodies)
+            .add_systems(Update, polylines::sync_polylines)
+            .add_systems(Update, terrain::sync_terrain)
+            .add_systems(Update, (joints::sync_joints, joints::remove_broken_joints))
+            .add_systems(
+                Update,
+                (motion::reset_motion_origins, motion::animate_solids).chain(),
+            );
     }
 }
 
 fn main() {
+    match HeadlessConfig::from_args(std::env::args().skip(1)) {
+        Ok(Some(config)) => headless::run(config),
+        Ok(None) => run_windowed(),
+        Err(error) => {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_windowed() {
     App::new()
         .add_plugins(DefaultPlugins)
-        .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.))
+        .add_plugins(SimulationPlugin)
         .add_plugins(RapierDebugRenderPlugin::default().disabled())
         .add_plugins(ResourceInspectorPlugin::<DebugInfo>::default())
-        .add_plugins(MainPlugin)
+        .add_plugins(FrameTimeDiagnosticsPlugin)
         .add_systems(Startup, setup_camera)
-        .add_systems(Startup, textures::generate_textures)
         .add_event::<ToolEvent>()
         .add_event::<CommandEvent>()
+        .add_event::<HistoryEvent>()
+        .add_event::<SelectionEvent>()
+        .add_event::<FitSceneEvent>()
+        .init_resource::<RubberBand>()
+        .init_resource::<Clipboard>()
+        .init_resource::<Snapping>()
+        .init_resource::<SnapTargets>()
+        .init_resource::<JointTool>()
+        .init_resource::<MaterialImages>()
+        .init_resource::<TimeControl>()
+        .init_resource::<SimulationStats>()
+        .init_resource::<StepTimer>()
+        .add_systems(PreUpdate, time_control::apply_time_control)
+        .add_systems(Update, time_control::handle_time_input)
         .add_systems(Update, ui::update_ui)
+        .add_systems(Update, ui::update_stats_window)
+        .add_systems(
+            PostUpdate,
+            (
+                stats::start_step_timer.before(PhysicsSet::StepSimulation),
+                stats::stop_step_timer.after(PhysicsSet::StepSimulation),
+                stats::collect_stats.after(stats::stop_step_timer),
+            ),
+        )
+        .add_systems(
+            Update,
+            (camera::pan_camera, camera::zoom_camera, camera::fit_scene)
+                .before(calculate_mouse_position),
+        )
         .add_systems(Update, calculate_mouse_position)
-        .add_systems(Update, handle_left_click.after(calculate_mouse_position))
-        .add_systems(Update, set_hover.after(calculate_mouse_position))
-        .add_systems(Update, highlight_hover.after(set_hover))
         .add_systems(
             Update,
-            balls::spawn_balls.run_if(on_timer(Duration::from_secs_f32(0.01))),
+            (
+                snapping::collect_snap_targets,
+                snapping::snap_mouse_position,
+            )
+                .chain()
+                .after(calculate_mouse_position),
         )
-        .add_systems(PostUpdate, balls::despawn_outside_world)
+        .add_systems(
+            Update,
+            handle_left_click.after(snapping::snap_mouse_position),
+        )
+        .add_systems(Update, set_hover.after(calculate_mouse_position))
+        .add_systems(Update, highlight_hover.after(set_hover))
         .add_systems(Update, toggle_debug_rendering)
         .add_systems(Update, handle_tool_events)
         .add_systems(Update, handle_command_events)
         .add_systems(PostUpdate, handle_input)
-        .add_systems(Update, scale)
-        .add_systems(Update, rotate)
-        .add_systems(Update, move_towards_mouse.after(calculate_mouse_position))
-        .add_systems(Update, move_to_mouse.after(calculate_mouse_position))
-        .add_systems(Update, apply_force_field)
+        .add_systems(Update, scale.after(snapping::snap_mouse_position))
+        .add_systems(Update, rotate.after(calculate_mouse_position))
+        .add_systems(
+            Update,
+            move_towards_mouse.after(snapping::snap_mouse_position),
+        )
+        .add_systems(Update, move_to_mouse.after(snapping::snap_mouse_position))
+        .add_systems(
+            Update,
+            polylines::draw_polylines.after(snapping::snap_mouse_position),
+        )
+        .add_systems(Update, snapping::draw_grid)
+        .add_systems(
+            Update,
+            (
+                materials::generate_material_images,
+                materials::apply_solid_materials,
+            )
+                .chain(),
+        )
+        .add_systems(Update, force_fields::draw_force_fields)
+        .add_systems(Update, ui::update_inspector)
+        .add_systems(Update, history::handle_history_events)
+        .add_systems(Update, selection::handle_selection_events)
+        .add_systems(Update, selection::update_rubber_band.after(set_hover))
+        .add_systems(Update, selection::draw_selection)
+        .add_systems(Update, world::draw_world_bounds)
+        .add_systems(
+            Update,
+            joints::handle_joint_tool.after(calculate_mouse_position),
+        )
+        .add_systems(Update, joints::draw_joints)
+        .add_systems(Update, counters::update_counter_labels)
+        .add_systems(Update, ui::update_counter_window)
+        .add_systems(Update, ui::update_joint_window)
         .run();
 }
 
 #[derive(Resource, Debug, Default)]
 struct Mouse {
     position: Vec2,
+    /// `position` after snapping, the same as `position` when snapping is off.
+    snapped: Vec2,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -92,7 +247,16 @@ struct Hoverable {
 struct OriginalColor(Color);
 
 fn set_hover(
-    mut query: Query<(&mut Hoverable, Entity, &GlobalTransform), With<Collider>>,
+    mut query: Query<
+        (
+            &mut Hoverable,
+            Entity,
+            &GlobalTransform,
+            Option<&Solid>,
+            Option<&TerrainHeights>,
+        ),
+        With<Collider>,
+    >,
     rapier_context: Query<&RapierContext>,
     mouse: Res<Mouse>,
 ) {
@@ -109,8 +273,10 @@ fn set_hover(
     // Find entity with highest z value
     let mut highest_entity: Option<Entity> = None;
     let mut highest_z = f32::NEG_INFINITY;
-    for (_, entity, transform) in &mut query {
-        if entities.contains(&entity) {
+    for (_, entity, transform, _, terrain) in &mut query {
+        let inverse = transform.compute_matrix().inverse();
+        let local = inverse.transform_point3(position.extend(0.)).truncate();
+        if entities.contains(&entity) || terrain.is_some_and(|terrain| terrain.contains(local)) {
             let z = transform.translation().z;
             if z > highest_z {
                 highest_z = z;
@@ -118,21 +284,12 @@ fn set_hover(
             }
         }
     }
-    for (mut hoverable, entity, transform) in &mut query {
+    for (mut hoverable, entity, transform, solid, _) in &mut query {
         if highest_entity == Some(entity) {
             let inverse = transform.compute_matrix().inverse();
             let transformed = inverse.transform_point3(position.extend(0.));
-            let x = transformed.x.abs();
-            let y = transformed.y.abs();
-            let x_is_center = x < 0.45;
-            let y_is_center = y < 0.45;
-            hoverable.position = if x_is_center && y_is_center {
-                Some(HoverPosition::Center)
-            } else if x_is_center || y_is_center {
-                Some(HoverPosition::Edge)
-            } else {
-                Some(HoverPosition::Corner)
-            };
+            let shape = solid.map(Solid::shape).unwrap_or_default();
+            hoverable.position = Some(shape.hover_position(transformed.truncate()));
         } else {
             hoverable.position = None;
         }
@@ -180,6 +337,10 @@ fn highlight_hover(
                     ctx.set_cursor_icon(egui::CursorIcon::ResizeVertical);
                     Some(fallback_color.with_alpha(0.9))
                 }
+                Some(Modifying::Scaling { .. }) => {
+                    ctx.set_cursor_icon(egui::CursorIcon::ResizeSouthEast);
+                    Some(fallback_color.with_alpha(0.9))
+                }
                 _ => None,
             }
         } else {
@@ -213,14 +374,17 @@ enum Mode {
     Default,
     Create,
     Modify,
+    Joint,
+    Draw,
 }
 
 #[derive(Component, Debug, Clone, Copy, PartialEq)]
 pub enum Modifying {
     Placing,
+    Drawing,
     Scaling { start: Vec2 },
     Moving { start: Vec2 },
-    Rotating { start: Vec2 },
+    Rotating { start: Vec2, pivot: Vec2 },
 }
 
 fn calculate_mouse_position(
@@ -242,35 +406,77 @@ fn calculate_mouse_position(
 fn move_to_mouse(mut query: Query<(&mut Transform, &Modifying)>, mouse: Res<Mouse>) {
     for (mut transform, modifying) in &mut query {
         if let Modifying::Placing = *modifying {
-            transform.translation.x = mouse.position.x;
-            transform.translation.y = mouse.position.y;
+            transform.translation.x = mouse.snapped.x;
+            transform.translation.y = mouse.snapped.y;
         }
     }
 }
 
+/// Drives moving solids towards where they started plus how far the mouse has moved, so a
+/// group keeps its layout. While the simulation is paused they are moved there directly, as are
+/// fixed bodies, which rapier doesn't move by velocity.
 fn move_towards_mouse(
-    mut query: Query<(&mut Velocity, &GlobalTransform, &Modifying)>,
+    mut query: Query<(
+        &mut Velocity,
+        &mut Transform,
+        &Modifying,
+        Option<&ModifyStart>,
+        Option<&RigidBody>,
+    )>,
     mouse: Res<Mouse>,
+    snapping: Res<Snapping>,
+    snap_targets: Res<SnapTargets>,
+    time_control: Res<TimeControl>,
 ) {
-    for (mut velocity, transform, modifying) in &mut query {
-        if let Modifying::Moving { start: _ } = *modifying {
-            let translation = transform.translation().truncate();
-            velocity.linvel = (mouse.position - translation) * 10.;
+    for (mut velocity, mut transform, modifying, modify_start, body) in &mut query {
+        if let Modifying::Moving { start } = *modifying {
+            let translation = transform.translation.truncate();
+            let target = match modify_start {
+                Some(ModifyStart(before)) => snapping.snap_moved(
+                    before.translation.truncate() + mouse.position - start,
+                    solid_bounds(&GlobalTransform::from(*before)).half_size(),
+                    &snap_targets,
+                ),
+                None => mouse.snapped,
+            };
+            if time_control.paused || body == Some(&RigidBody::Fixed) {
+                transform.translation = target.extend(transform.translation.z);
+                velocity.linvel = Vec2::ZERO;
+            } else {
+                velocity.linvel = (target - translation) * 10.;
+            }
         }
     }
 }
 
 fn handle_left_click(
     mouse_input: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
     mode: Res<Mode>,
     mouse: Res<Mouse>,
     mut event_writer: EventWriter<CommandEvent>,
-    query: Query<(Entity, &Hoverable)>,
+    mut selection_writer: EventWriter<SelectionEvent>,
+    query: Query<(Entity, &Hoverable, &GlobalTransform, Has<Selected>)>,
+    mut egui_contexts: EguiContexts,
 ) {
+    if egui_contexts.ctx_mut().is_pointer_over_area() || is_panning(&mouse_input, &keyboard_input) {
+        return;
+    }
     if mouse_input.just_pressed(MouseButton::Left) {
         match *mode {
             Mode::Default => {
-                for (entity, hoverable) in &query {
+                let shift = shift_pressed(&keyboard_input);
+                for (entity, hoverable, transform, selected) in &query {
+                    if hoverable.position.is_none() {
+                        continue;
+                    }
+                    if shift {
+                        selection_writer.send(SelectionEvent::Toggle(entity));
+                        continue;
+                    }
+                    if !selected {
+                        selection_writer.send(SelectionEvent::Replace(vec![entity]));
+                    }
                     match hoverable.position {
                         Some(HoverPosition::Center) => {
                             event_writer.send(CommandEvent {
@@ -288,44 +494,76 @@ fn handle_left_click(
                                 },
                             });
                         }
-                        _ => {}
+                        Some(HoverPosition::Corner) => {
+                            let matrix = transform.compute_matrix();
+                            let local =
+                                matrix.inverse().transform_point3(mouse.position.extend(0.));
+                            let opposite =
+                                Vec3::new(-0.5 * local.x.signum(), -0.5 * local.y.signum(), 0.);
+                            event_writer.send(CommandEvent {
+                                command: Resize {
+                                    entity,
+                                    start: matrix.transform_point3(opposite).truncate(),
+                                },
+                            });
+                        }
+                        None => {}
                     }
                 }
             }
             Mode::Create => {
                 event_writer.send(CommandEvent {
                     command: Created {
-                        position: mouse.position,
+                        position: mouse.snapped,
                     },
                 });
             }
             Mode::Modify => {
                 event_writer.send(CommandEvent { command: Scaled });
             }
+            Mode::Joint | Mode::Draw => {}
         }
     }
 }
 
+/// Scales a solid so that `start` and the mouse are opposite corners, measured along the
+/// solid's own axes so rotated solids keep their rotation.
+/// Smallest width or height a solid can be resized to, in pixels, so it never collapses to a
+/// zero scale.
+const MIN_SIZE: f32 = 1.;
+
 fn scale(mut query: Query<(&mut Transform, &Modifying)>, mouse: Res<Mouse>) {
-    let position = mouse.position;
+    let position = mouse.snapped;
     for (mut transform, modifying) in &mut query {
         if let Modifying::Scaling { start } = modifying {
             transform.translation.x = (position.x + start.x) / 2.;
             transform.translation.y = (position.y + start.y) / 2.;
 
-            transform.scale.x = ((position.x - transform.translation.x) * 2.).abs();
-            transform.scale.y = ((position.y - transform.translation.y) * 2.).abs();
+            let diagonal = transform.rotation.inverse() * (position - *start).extend(0.);
+            transform.scale.x = diagonal.x.abs().max(MIN_SIZE);
+            transform.scale.y = diagonal.y.abs().max(MIN_SIZE);
         }
     }
 }
 
-fn rotate(mouse: Res<Mouse>, mut query: Query<(&mut Transform, &Modifying)>) {
-    let position = mouse.position;
-    for (mut transform, modifying) in &mut query {
-        if let Modifying::Rotating { start: _ } = modifying {
-            transform.rotation = Quat::from_rotation_z(
-                -(position - transform.translation.truncate()).angle_to(Vec2::new(1.0, 0.0)),
-            );
+/// Rotates solids around their common pivot by the angle the mouse has swept since the
+/// rotation started, in fixed increments while Shift is held.
+fn rotate(
+    mouse: Res<Mouse>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    snapping: Res<Snapping>,
+    mut query: Query<(&mut Transform, &Modifying, &ModifyStart)>,
+) {
+    for (mut transform, modifying, ModifyStart(before)) in &mut query {
+        if let Modifying::Rotating { start, pivot } = *modifying {
+            let mut angle = (start - pivot).angle_to(mouse.position - pivot);
+            if shift_pressed(&keyboard_input) {
+                angle = snapping.snap_angle(angle);
+            }
+            let rotation = Quat::from_rotation_z(angle);
+            let offset = rotation * (before.translation - pivot.extend(before.translation.z));
+            transform.rotation = rotation * before.rotation;
+            transform.translation = pivot.extend(before.translation.z) + offset;
         }
     }
 }
@@ -333,21 +571,67 @@ fn rotate(mouse: Res<Mouse>, mut query: Query<(&mut Transform, &Modifying)>) {
 #[derive(EnumIter, Copy, Clone, PartialEq, Eq, Hash, Debug)]
 enum Tool {
     Box,
+    Circle,
+    Capsule,
+    Triangle,
+    Polygon,
+    Polyline,
+    Terrain,
     ForceField,
+    Emitter,
+    Counter,
+    Joint,
 }
 
 impl Tool {
     fn key(&self) -> KeyCode {
         match self {
             Tool::Box => KeyCode::KeyB,
+            Tool::Circle => KeyCode::KeyC,
+            Tool::Capsule => KeyCode::KeyK,
+            Tool::Triangle => KeyCode::KeyT,
+            Tool::Polygon => KeyCode::KeyP,
+            Tool::Polyline => KeyCode::KeyL,
+            Tool::Terrain => KeyCode::KeyG,
             Tool::ForceField => KeyCode::KeyF,
+            Tool::Emitter => KeyCode::KeyE,
+            Tool::Counter => KeyCode::KeyN,
+            Tool::Joint => KeyCode::KeyJ,
         }
     }
 
     fn label(&self) -> &str {
         match self {
             Tool::Box => "Box",
+            Tool::Circle => "Circle",
+            Tool::Capsule => "Capsule",
+            Tool::Triangle => "Triangle",
+            Tool::Polygon => "Polygon",
+            Tool::Polyline => "Polyline",
+            Tool::Terrain => "Terrain",
             Tool::ForceField => "Force Field",
+            Tool::Emitter => "Emitter",
+            Tool::Counter => "Counter",
+            Tool::Joint => "Joint",
+        }
+    }
+
+    /// Shape of the solid body the tool creates, if it creates one.
+    fn shape(&self, defaults: &BoxDefaults) -> Option<SolidShape> {
+        match self {
+            Tool::Box => Some(SolidShape::Rectangle),
+            Tool::Circle => Some(SolidShape::Circle),
+            Tool::Capsule => Some(SolidShape::Capsule),
+            Tool::Triangle => Some(SolidShape::Triangle),
+            Tool::Polygon => Some(SolidShape::Polygon {
+                sides: defaults.polygon_sides,
+            }),
+            Tool::Polyline
+            | Tool::Terrain
+            | Tool::ForceField
+            | Tool::Emitter
+            | Tool::Counter
+            | Tool::Joint => None,
         }
     }
 }
@@ -362,8 +646,24 @@ enum Command {
     Scaled,
     Move { entity: Entity, start: Vec2 },
     Rotate { entity: Entity, start: Vec2 },
+    Resize { entity: Entity, start: Vec2 },
+    Delete { entities: Vec<Entity> },
+    Duplicate { entities: Vec<Entity> },
+    CopyToClipboard { entities: Vec<Entity> },
+    PasteFromClipboard { position: Vec2 },
 }
 
+/// Solids copied with Ctrl+C, pasted centered on the mouse with Ctrl+V, along with the joints
+/// between them.
+#[derive(Resource, Debug, Default)]
+struct Clipboard {
+    solids: Vec<SceneSolid>,
+    joints: Vec<SceneJoint>,
+}
+
+/// How far duplicates are placed from their originals.
+const DUPLICATE_OFFSET: Vec2 = Vec2::new(20., -20.);
+
 #[derive(Event)]
 struct CommandEvent {
     command: Command,
@@ -377,109 +677,455 @@ pub struct DebugInfo {
     transform: Transform,
 }
 
-fn apply_force_field(
-    rapier_context: ReadDefaultRapierContext,
-    query: Query<(&GlobalTransform, &Solid, &Collider)>,
-    balls_query: Query<(Entity), With<Ball>>,
-    mut commands: Commands,
-    mut gizmos: Gizmos,
-    mut debug_info: ResMut<DebugInfo>,
-) {
-    for (entity) in &balls_query {
-        commands.entity(entity).insert(ExternalForce {
-            force: Vec2::new(0.0, 0.0),
-            ..default()
+/// Rotates a force field's local force vector into world space.
+fn rotate_force(force: Vec2, z_rotation: f32) -> Vec2 {
+    Vec2::new(
+        force.x * z_rotation.cos() - force.y * z_rotation.sin(),
+        force.x * z_rotation.sin() + force.y * z_rotation.cos(),
+    )
+}
+
+/// Transform of a solid when a move or rotation started, used to record it in the history.
+#[derive(Component)]
+struct ModifyStart(Transform);
+
+/// Spawns `copies` moved by `offset` on top of all other solids, records them as a single
+/// history step and returns the new entities.
+fn spawn_copies(
+    copies: &[SceneSolid],
+    joints: &[SceneJoint],
+    offset: Vec2,
+    commands: &mut Commands,
+    meshes: &Meshes,
+    materials: &mut Assets<ColorMaterial>,
+    z_counter: &mut ZCounter,
+    history: &mut History,
+) -> Vec<Entity> {
+    let mut step = HistoryStep::default();
+    let mut entities = Vec::new();
+    for copy in copies {
+        let mut copy = copy.clone();
+        copy.transform.translation += offset.extend(0.);
+        copy.transform.translation.z = z_counter.0;
+        z_counter.0 += 0.01;
+        let entity = copy.spawn(commands, meshes, materials);
+        step.push(HistoryEntry::Created {
+            entity,
+            solid: copy,
         });
+        entities.push(entity);
     }
+    for joint in joints {
+        let Some(joint) = joint.map(|index| entities.get(*index).copied()) else {
+            continue;
+        };
+        let joint = joint.offset(offset);
+        joint.spawn(commands);
+        step.push(HistoryEntry::Joint {
+            joint,
+            created: true,
+        });
+    }
+    history.push(step);
+    entities
+}
 
-    for (transform, solid, collider) in &query {
-        if let Solid::ForceField { force } = solid {
-            let (_, rotation, translation) = transform.to_scale_rotation_translation();
-            let z_rotation = rotation.to_euler(EulerRot::ZYX).0;
-            let rotated_force = Vec2::new(
-                force.x * z_rotation.cos() - force.y * z_rotation.sin(),
-                force.x * z_rotation.sin() + force.y * z_rotation.cos(),
-            );
-            debug_info.transform = transform.compute_transform();
-            debug_info.rotation = rotation;
-            debug_info.rotation_z = z_rotation;
-            gizmos.ray_2d(translation.truncate(), rotated_force * 100.0, Color::WHITE);
-            rapier_context.intersections_with_shape(
-                translation.truncate(),
-                z_rotation,
-                collider,
-                QueryFilter::default(),
-                |entity| {
-                    commands.get_entity(entity).map(|mut commands| {
-                        commands.insert(ExternalForce {
-                            force: rotated_force,
-                            ..default()
-                        });
-                    });
-                    true
-                },
-            );
-        }
+/// The whole selection when `entity` is part of it, otherwise just `entity`.
+fn command_targets(entity: Entity, selected: &Query<Entity, With<Selected>>) -> Vec<Entity> {
+    if selected.contains(entity) {
+        selected.iter().collect()
+    } else {
+        vec![entity]
     }
 }
 
 fn handle_command_events(
     mut event_reader: EventReader<CommandEvent>,
     mut commands: Commands,
-    query: Query<(Entity, &Solid), With<Modifying>>,
+    mut history: ResMut<History>,
+    mut selection_writer: EventWriter<SelectionEvent>,
+    mut z_counter: ResMut<ZCounter>,
+    meshes: Res<Meshes>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut clipboard: ResMut<Clipboard>,
+    query: Query<
+        (
+            Entity,
+            &Solid,
+            &Modifying,
+            &Transform,
+            Option<&MeshVariant>,
+            Option<&OriginalColor>,
+            Option<&ModifyStart>,
+        ),
+        With<Modifying>,
+    >,
+    solids: Query<
+        (
+            &Solid,
+            &Transform,
+            Option<&MeshVariant>,
+            Option<&OriginalColor>,
+        ),
+        Without<Modifying>,
+    >,
+    selected: Query<Entity, With<Selected>>,
+    joints: Query<&Joint>,
+    anchors: Query<&Transform, With<WorldAnchor>>,
 ) {
+    let snapshot = |entities: &[Entity]| -> Clipboard {
+        let entities: Vec<Entity> = entities
+            .iter()
+            .copied()
+            .filter(|entity| solids.contains(*entity))
+            .collect();
+        Clipboard {
+            solids: entities
+                .iter()
+                .filter_map(|entity| solids.get(*entity).ok())
+                .map(|(solid, transform, mesh, color)| {
+                    SceneSolid::from_components(solid, transform, mesh, color)
+                })
+                .collect(),
+            joints: joints_between(&entities, &joints, &anchors),
+        }
+    };
     for event in event_reader.read() {
-        match event.command {
+        match &event.command {
             Created { position } => {
-                for (entity, _) in &query {
+                for (entity, ..) in &query {
                     commands
                         .entity(entity)
-                        .insert(Modifying::Scaling { start: position });
+                        .insert(Modifying::Scaling { start: *position });
                 }
                 commands.insert_resource(Mode::Modify);
             }
             Scaled => {
-                for (entity, solid) in &query {
-                    commands
-                        .entity(entity)
-                        .remove::<Modifying>()
-                        .insert(Velocity::default())
-                        .insert(Collider::cuboid(0.5, 0.5));
-
-                    match solid {
-                        Solid::Box => {
-                            commands
-                                .entity(entity)
-                                .insert(RigidBody::KinematicVelocityBased);
+                let mut step = HistoryStep::default();
+                for (entity, solid, modifying, transform, mesh, color, modify_start) in &query {
+                    let mut entity_commands = commands.entity(entity);
+                    entity_commands.remove::<(Modifying, ModifyStart)>();
+                    insert_physics(&mut entity_commands, solid);
+
+                    match (modifying, modify_start) {
+                        (Modifying::Scaling { .. } | Modifying::Drawing, None) => {
+                            step.push(HistoryEntry::Created {
+                                entity,
+                                solid: SceneSolid::from_components(solid, transform, mesh, color),
+                            })
                         }
-                        Solid::ForceField { .. } => {
-                            commands
-                                .entity(entity)
-                                .insert(RigidBody::KinematicVelocityBased);
-                            commands.entity(entity).insert(Sensor);
+                        (_, Some(ModifyStart(before))) if before != transform => {
+                            step.push(HistoryEntry::Transformed {
+                                entity,
+                                before: *before,
+                                after: *transform,
+                            })
                         }
+                        _ => {}
                     }
                 }
+                history.push(step);
                 commands.insert_resource(Mode::Default);
             }
             Move { start, entity } => {
-                commands.entity(entity).insert(Modifying::Moving { start });
+                for target in command_targets(*entity, &selected) {
+                    if let Ok((_, transform, ..)) = solids.get(target) {
+                        commands
+                            .entity(target)
+                            .insert((ModifyStart(*transform), Modifying::Moving { start: *start }));
+                    }
+                }
+                commands.insert_resource(Mode::Modify);
+            }
+            Resize { start, entity } => {
+                if let Ok((_, transform, ..)) = solids.get(*entity) {
+                    commands.entity(*entity).insert((
+                        ModifyStart(*transform),
+                        Modifying::Scaling { start: *start },
+                    ));
+                }
                 commands.insert_resource(Mode::Modify);
             }
             Rotate { start, entity } => {
-                commands
-                    .entity(entity)
-                    .insert(Modifying::Rotating { start });
+                let targets: Vec<(Entity, Transform)> = command_targets(*entity, &selected)
+                    .into_iter()
+                    .filter_map(|target| {
+                        let (_, transform, ..) = solids.get(target).ok()?;
+                        Some((target, *transform))
+                    })
+                    .collect();
+                let pivot = targets
+                    .iter()
+                    .map(|(_, transform)| transform.translation.truncate())
+                    .sum::<Vec2>()
+                    / targets.len().max(1) as f32;
+                for (target, transform) in targets {
+                    commands.entity(target).insert((
+                        ModifyStart(transform),
+                        Modifying::Rotating {
+                            start: *start,
+                            pivot,
+                        },
+                    ));
+                }
                 commands.insert_resource(Mode::Modify);
             }
+            Delete { entities } => {
+                let mut step = HistoryStep::default();
+                for entity in entities {
+                    if let Ok((solid, transform, mesh, color)) = solids.get(*entity) {
+                        step.push(HistoryEntry::Deleted {
+                            entity: *entity,
+                            solid: SceneSolid::from_components(solid, transform, mesh, color),
+                        });
+                        commands.entity(*entity).despawn();
+                    }
+                }
+                // Joints go away with their solids, so keep them for undoing the delete.
+                for joint in &joints {
+                    if joint.bodies.iter().any(|body| entities.contains(body)) {
+                        step.push(HistoryEntry::Joint {
+                            joint: SceneJoint::from_joint(joint, &anchors),
+                            created: false,
+                        });
+                    }
+                }
+                history.push(step);
+            }
+            Duplicate { entities } => {
+                let copies = snapshot(entities);
+                let duplicates = spawn_copies(
+                    &copies.solids,
+                    &copies.joints,
+                    DUPLICATE_OFFSET,
+                    &mut commands,
+                    &meshes,
+                    &mut materials,
+                    &mut z_counter,
+                    &mut history,
+                );
+                selection_writer.send(SelectionEvent::Replace(duplicates));
+            }
+            CopyToClipboard { entities } => {
+                *clipboard = snapshot(entities);
+            }
+            PasteFromClipboard { position } => {
+                if clipboard.solids.is_empty() {
+                    continue;
+                }
+                let center = clipboard
+                    .solids
+                    .iter()
+                    .map(|copy| copy.transform.translation.truncate())
+                    .sum::<Vec2>()
+                    / clipboard.solids.len() as f32;
+                let pasted = spawn_copies(
+                    &clipboard.solids,
+                    &clipboard.joints,
+                    *position - center,
+                    &mut commands,
+                    &meshes,
+                    &mut materials,
+                    &mut z_counter,
+                    &mut history,
+                );
+                selection_writer.send(SelectionEvent::Replace(pasted));
+            }
         }
     }
 }
 
-#[derive(Component)]
+#[derive(Component, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "SavedSolid")]
 enum Solid {
-    Box,
-    ForceField { force: Vec2 },
+    Box {
+        shape: SolidShape,
+        body: BodyType,
+        density: f32,
+        motion: Motion,
+        /// Index into the `MaterialLibrary`, vertex colors when `None`.
+        material: Option<usize>,
+    },
+    ForceField {
+        /// Direction of the force in the field's local space, in radians. Kept apart from the
+        /// magnitude so it survives the magnitude being set to zero.
+        direction: f32,
+        magnitude: f32,
+        /// How much weaker the force is at the edge than at the center, from 0 to 1.
+        falloff: f32,
+        kind: FieldKind,
+    },
+    Emitter(EmitterSettings),
+    /// A sensor that counts the balls entering it.
+    Counter(CounterSettings),
+    /// A stroke through `points`, given in the unit square like the outlines of shapes.
+    Polyline {
+        points: Vec<Vec2>,
+    },
+    Terrain(TerrainSettings),
+}
+
+/// A `Solid` as read from a scene file, also accepting the fields of older scenes.
+#[derive(Deserialize)]
+#[serde(rename = "Solid")]
+enum SavedSolid {
+    Box {
+        #[serde(default)]
+        shape: SolidShape,
+        #[serde(default)]
+        body: BodyType,
+        #[serde(default = "default_density")]
+        density: f32,
+        #[serde(default)]
+        motion: Motion,
+        #[serde(default)]
+        material: Option<usize>,
+    },
+    ForceField {
+        #[serde(default)]
+        direction: Option<f32>,
+        #[serde(default)]
+        magnitude: Option<f32>,
+        /// The force as a single vector, as saved before direction and magnitude were split.
+        #[serde(default)]
+        force: Option<Vec2>,
+        #[serde(default)]
+        falloff: f32,
+        #[serde(default)]
+        kind: FieldKind,
+    },
+    Emitter(EmitterSettings),
+    Counter(CounterSettings),
+    Polyline {
+        points: Vec<Vec2>,
+    },
+    Terrain(TerrainSettings),
+}
+
+fn default_density() -> f32 {
+    BoxDefaults::default().density
+}
+
+impl From<SavedSolid> for Solid {
+    fn from(saved: SavedSolid) -> Self {
+        match saved {
+            SavedSolid::Box {
+                shape,
+                body,
+                density,
+                motion,
+                material,
+            } => Solid::Box {
+                shape,
+                body,
+                density,
+                motion,
+                material,
+            },
+            SavedSolid::ForceField {
+                direction,
+                magnitude,
+                force,
+                falloff,
+                kind,
+            } => {
+                let (direction, magnitude) = match force {
+                    Some(force) => (force.to_angle(), force.length()),
+                    None => (direction.unwrap_or_default(), magnitude.unwrap_or_default()),
+                };
+                Solid::ForceField {
+                    direction,
+                    magnitude,
+                    falloff,
+                    kind,
+                }
+            }
+            SavedSolid::Emitter(settings) => Solid::Emitter(settings),
+            SavedSolid::Counter(settings) => Solid::Counter(settings),
+            SavedSolid::Polyline { points } => Solid::Polyline { points },
+            SavedSolid::Terrain(settings) => Solid::Terrain(settings),
+        }
+    }
+}
+
+impl Solid {
+    fn label(&self) -> &str {
+        match self {
+            Solid::Box { shape, .. } => shape.label(),
+            Solid::ForceField { .. } => "Force Field",
+            Solid::Emitter(_) => "Emitter",
+            Solid::Counter(_) => "Counter",
+            Solid::Polyline { .. } => "Polyline",
+            Solid::Terrain(_) => "Terrain",
+        }
+    }
+
+    fn shape(&self) -> SolidShape {
+        match self {
+            Solid::Box { shape, .. } => *shape,
+            Solid::ForceField { .. }
+            | Solid::Emitter(_)
+            | Solid::Counter(_)
+            | Solid::Polyline { .. }
+            | Solid::Terrain(_) => SolidShape::Rectangle,
+        }
+    }
+
+    /// Keeps a box pointing at the same material after the one at `removed` is taken out of the
+    /// `MaterialLibrary`, falling back to vertex colors if it used the removed one.
+    fn remove_material(&mut self, removed: usize) {
+        let Solid::Box { material, .. } = self else {
+            return;
+        };
+        match *material {
+            Some(index) if index == removed => *material = None,
+            Some(index) if index > removed => *material = Some(index - 1),
+            _ => {}
+        }
+    }
+}
+
+/// Inserts the rigid body and collider for a solid that is done being placed or modified.
+fn insert_physics(entity_commands: &mut EntityCommands, solid: &Solid) {
+    entity_commands.insert(Velocity::default());
+
+    match solid {
+        Solid::Box {
+            shape,
+            body,
+            density,
+            ..
+        } => {
+            entity_commands.insert((
+                shape.collider(),
+                body.rigid_body(),
+                ColliderMassProperties::Density(*density),
+            ));
+        }
+        Solid::ForceField { .. } | Solid::Emitter(_) => {
+            entity_commands.insert(Collider::cuboid(0.5, 0.5));
+            entity_commands.insert(RigidBody::KinematicVelocityBased);
+            entity_commands.insert(Sensor);
+        }
+        Solid::Counter(_) => {
+            entity_commands.insert((
+                Collider::cuboid(0.5, 0.5),
+                RigidBody::KinematicVelocityBased,
+                Sensor,
+                ActiveEvents::COLLISION_EVENTS,
+            ));
+        }
+        Solid::Terrain(settings) => {
+            entity_commands.insert((settings.collider(), RigidBody::KinematicVelocityBased));
+        }
+        Solid::Polyline { .. } => {
+            // The collider is built in pixels by `sync_polylines` so the stroke keeps its width.
+            entity_commands.insert((
+                RigidBody::KinematicVelocityBased,
+                ColliderScale::Absolute(Vec2::ONE),
+            ));
+        }
+    }
 }
 
 fn handle_tool_events(
@@ -489,18 +1135,28 @@ fn handle_tool_events(
     mut event_reader: EventReader<ToolEvent>,
     mut commands: Commands,
     mut z_counter: ResMut<ZCounter>,
+    box_defaults: Res<BoxDefaults>,
 ) {
     for event in event_reader.read() {
         match *mode {
             Mode::Default => match event.tool {
-                Tool::Box => {
+                Tool::Box | Tool::Circle | Tool::Capsule | Tool::Triangle | Tool::Polygon => {
+                    let shape = event.tool.shape(&box_defaults).unwrap_or_default();
                     let material = materials.add(ColorMaterial::default());
+                    let (variant, mesh) = meshes.get_random(shape);
 
                     commands.spawn((
-                        Solid::Box,
+                        Solid::Box {
+                            shape,
+                            body: box_defaults.body,
+                            density: box_defaults.density,
+                            motion: Motion::None,
+                            material: None,
+                        },
                         Hoverable::default(),
                         Modifying::Placing,
-                        meshes.get_random(),
+                        variant,
+                        mesh,
                         MeshMaterial2d(material),
                         Transform::from_xyz(0.0, 0.0, z_counter.0).with_scale(Vec3::splat(10.)),
                     ));
@@ -511,7 +1167,10 @@ fn handle_tool_events(
                     let color = Color::srgba(0.0, 0.0, 1.0, 0.1);
                     commands.spawn((
                         Solid::ForceField {
-                            force: Vec2::new(0.0, 0.5),
+                            direction: FRAC_PI_2,
+                            magnitude: 0.5,
+                            falloff: 0.0,
+                            kind: FieldKind::Linear,
                         },
                         OriginalColor(color),
                         Hoverable::default(),
@@ -522,6 +1181,68 @@ fn handle_tool_events(
                     z_counter.0 += 0.01;
                     commands.insert_resource(Mode::Create);
                 }
+                Tool::Emitter => {
+                    commands.spawn((
+                        Solid::Emitter(EmitterSettings::default()),
+                        OriginalColor(EMITTER_COLOR),
+                        Hoverable::default(),
+                        Modifying::Placing,
+                        Sprite {
+                            color: EMITTER_COLOR,
+                            ..default()
+                        },
+                        Transform::from_xyz(0.0, 0.0, z_counter.0).with_scale(Vec3::splat(10.)),
+                    ));
+                    z_counter.0 += 0.01;
+                    commands.insert_resource(Mode::Create);
+                }
+                Tool::Counter => {
+                    commands.spawn((
+                        Solid::Counter(CounterSettings::default()),
+                        CounterState::default(),
+                        OriginalColor(COUNTER_COLOR),
+                        Hoverable::default(),
+                        Modifying::Placing,
+                        Sprite {
+                            color: COUNTER_COLOR,
+                            ..default()
+                        },
+                        Transform::from_xyz(0.0, 0.0, z_counter.0).with_scale(Vec3::splat(10.)),
+                    ));
+                    z_counter.0 += 0.01;
+                    commands.insert_resource(Mode::Create);
+                }
+                Tool::Polyline => {
+                    commands.spawn((
+                        Solid::Polyline { points: Vec::new() },
+                        OriginalColor(POLYLINE_COLOR),
+                        Hoverable::default(),
+                        Modifying::Drawing,
+                        DrawnPath::default(),
+                        MeshMaterial2d(materials.add(ColorMaterial::from_color(POLYLINE_COLOR))),
+                        Transform::from_xyz(0.0, 0.0, z_counter.0),
+                    ));
+                    z_counter.0 += 0.01;
+                    commands.insert_resource(Mode::Draw);
+                }
+                Tool::Terrain => {
+                    commands.spawn((
+                        Solid::Terrain(TerrainSettings {
+                            seed: rand::random(),
+                            ..default()
+                        }),
+                        OriginalColor(TERRAIN_COLOR),
+                        Hoverable::default(),
+                        Modifying::Placing,
+                        MeshMaterial2d(materials.add(ColorMaterial::from_color(TERRAIN_COLOR))),
+                        Transform::from_xyz(0.0, 0.0, z_counter.0).with_scale(Vec3::splat(10.)),
+                    ));
+                    z_counter.0 += 0.01;
+                    commands.insert_resource(Mode::Create);
+                }
+                Tool::Joint => {
+                    commands.insert_resource(Mode::Joint);
+                }
             },
             _ => {}
         }
@@ -532,14 +1253,93 @@ fn handle_input(
     mut commands: Commands,
     keyboard_input: Res<ButtonInput<KeyCode>>,
     mut event_sender: EventWriter<ToolEvent>,
-    query: Query<Entity, With<Modifying>>,
+    mut scene_event_sender: EventWriter<SceneEvent>,
+    mut history_event_sender: EventWriter<HistoryEvent>,
+    mut command_sender: EventWriter<CommandEvent>,
+    mut fit_scene_sender: EventWriter<FitSceneEvent>,
+    mode: Res<Mode>,
+    mouse: Res<Mouse>,
+    query: Query<(Entity, &Solid, Option<&ModifyStart>), With<Modifying>>,
+    selected: Query<Entity, With<Selected>>,
+    hoverables: Query<(Entity, &Hoverable)>,
+    mut egui_contexts: EguiContexts,
 ) {
+    if egui_contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
     if keyboard_input.just_pressed(KeyCode::Escape) {
-        for entity in &query {
-            commands.entity(entity).despawn();
+        // Solids that were already placed go back to where they were, new ones are dropped.
+        for (entity, solid, modify_start) in &query {
+            let mut entity_commands = commands.entity(entity);
+            match modify_start {
+                Some(ModifyStart(before)) => {
+                    entity_commands
+                        .insert(*before)
+                        .remove::<(Modifying, ModifyStart)>();
+                    insert_physics(&mut entity_commands, solid);
+                }
+                None => entity_commands.despawn(),
+            }
         }
         commands.insert_resource(Mode::Default);
     }
+    // Edit commands apply to the selection, or to the hovered solid when nothing is selected.
+    let mut targets: Vec<Entity> = selected.iter().collect();
+    if targets.is_empty() {
+        targets.extend(
+            hoverables
+                .iter()
+                .filter(|(_, hoverable)| hoverable.position.is_some())
+                .map(|(entity, _)| entity),
+        );
+    }
+    let can_edit = *mode == Mode::Default;
+    let can_edit_targets = can_edit && !targets.is_empty();
+    if can_edit_targets && keyboard_input.any_just_pressed([KeyCode::Delete, KeyCode::Backspace]) {
+        command_sender.send(CommandEvent {
+            command: Delete {
+                entities: targets.clone(),
+            },
+        });
+    }
+    if keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        if can_edit_targets && keyboard_input.just_pressed(KeyCode::KeyD) {
+            command_sender.send(CommandEvent {
+                command: Duplicate {
+                    entities: targets.clone(),
+                },
+            });
+        }
+        if can_edit_targets && keyboard_input.just_pressed(KeyCode::KeyC) {
+            command_sender.send(CommandEvent {
+                command: CopyToClipboard { entities: targets },
+            });
+        }
+        if can_edit && keyboard_input.just_pressed(KeyCode::KeyV) {
+            command_sender.send(CommandEvent {
+                command: PasteFromClipboard {
+                    position: mouse.position,
+                },
+            });
+        }
+        if keyboard_input.just_pressed(KeyCode::KeyS) {
+            scene_event_sender.send(SceneEvent::Save);
+        }
+        if keyboard_input.just_pressed(KeyCode::KeyO) {
+            scene_event_sender.send(SceneEvent::Load);
+        }
+        if keyboard_input.just_pressed(KeyCode::KeyZ) {
+            if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+                history_event_sender.send(HistoryEvent::Redo);
+            } else {
+                history_event_sender.send(HistoryEvent::Undo);
+            }
+        }
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::Home) {
+        fit_scene_sender.send(FitSceneEvent);
+    }
     for tool in Tool::iter() {
         if keyboard_input.just_pressed(tool.key()) {
             event_sender.send(ToolEvent { tool });
@@ -0,0 +1,178 @@
+use bevy::color::palettes::css::WHITE;
+use bevy::prelude::*;
+use bevy::render::mesh::VertexAttributeValues;
+use serde::{Deserialize, Serialize};
+
+use crate::perlin::{create_perlin_image, Perlin};
+use crate::shapes::SolidShape;
+use crate::textures::{MeshVariant, Meshes};
+use crate::Solid;
+
+/// A noise texture that solids can be painted with.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NoiseMaterial {
+    pub name: String,
+    pub seed: u32,
+    /// Noise cells across one tile of the texture.
+    pub scale: f32,
+    pub octaves: u32,
+    pub tint: Color,
+    /// Size of one tile in pixels, so the texture keeps its size when a solid is resized.
+    pub tile_size: f32,
+}
+
+impl Default for NoiseMaterial {
+    fn default() -> Self {
+        NoiseMaterial {
+            name: "Material".to_string(),
+            seed: 0,
+            scale: 4.,
+            octaves: 4,
+            tint: Color::WHITE,
+            tile_size: 100.,
+        }
+    }
+}
+
+impl NoiseMaterial {
+    fn image(&self) -> Image {
+        create_perlin_image(&Perlin::new(self.seed), self.scale, self.octaves, self.tint)
+    }
+}
+
+/// Materials that solids refer to by index, saved along with the scene.
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MaterialLibrary {
+    pub materials: Vec<NoiseMaterial>,
+}
+
+impl Default for MaterialLibrary {
+    fn default() -> Self {
+        let material = |name: &str, seed, scale, octaves, tint| NoiseMaterial {
+            name: name.to_string(),
+            seed,
+            scale,
+            octaves,
+            tint,
+            ..default()
+        };
+        MaterialLibrary {
+            materials: vec![
+                material("Stone", 1, 4., 5, Color::srgb(0.7, 0.7, 0.75)),
+                material("Grass", 2, 8., 3, Color::srgb(0.4, 0.8, 0.3)),
+                material("Sand", 3, 16., 2, Color::srgb(0.95, 0.85, 0.6)),
+                material("Marble", 4, 2., 6, Color::srgb(0.95, 0.95, 1.0)),
+            ],
+        }
+    }
+}
+
+impl MaterialLibrary {
+    pub fn name(&self, index: Option<usize>) -> &str {
+        index
+            .and_then(|index| self.materials.get(index))
+            .map_or("None", |material| material.name.as_str())
+    }
+}
+
+/// Textures of the library's materials, along with the settings they were generated from.
+#[derive(Resource, Debug, Default)]
+pub struct MaterialImages(Vec<(NoiseMaterial, Handle<Image>)>);
+
+/// Shape and tiles across of a textured solid that its mesh was built for.
+#[derive(Component, Debug, PartialEq)]
+pub struct TextureTiling {
+    shape: SolidShape,
+    tiles: Vec2,
+}
+
+/// Regenerates the textures of materials that were added or edited.
+pub fn generate_material_images(
+    library: Res<MaterialLibrary>,
+    mut material_images: ResMut<MaterialImages>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if !library.is_changed() {
+        return;
+    }
+    material_images.0.truncate(library.materials.len());
+    for (index, material) in library.materials.iter().enumerate() {
+        match material_images.0.get_mut(index) {
+            Some((generated, _)) if generated == material => {}
+            Some(entry) => *entry = (material.clone(), images.add(material.image())),
+            None => material_images
+                .0
+                .push((material.clone(), images.add(material.image()))),
+        }
+    }
+}
+
+/// Gives boxes with a material a white mesh whose texture coordinates repeat every tile, and
+/// restores the vertex colored mesh of boxes without one.
+pub fn apply_solid_materials(
+    mut commands: Commands,
+    material_images: Res<MaterialImages>,
+    meshes: Res<Meshes>,
+    mut mesh_assets: ResMut<Assets<Mesh>>,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+    query: Query<(
+        Entity,
+        &Solid,
+        &Transform,
+        &MeshMaterial2d<ColorMaterial>,
+        Option<&MeshVariant>,
+        Option<&TextureTiling>,
+    )>,
+) {
+    for (entity, solid, transform, material, variant, current_tiling) in &query {
+        let Solid::Box {
+            shape,
+            material: index,
+            ..
+        } = solid
+        else {
+            continue;
+        };
+        // Materials removed from the library may leave solids pointing past its end.
+        let Some((noise, image)) = index.and_then(|index| material_images.0.get(index)) else {
+            if current_tiling.is_some() {
+                commands
+                    .entity(entity)
+                    .remove::<TextureTiling>()
+                    .insert(meshes.get(*shape, variant.copied().unwrap_or(MeshVariant(0))));
+                if let Some(material) = color_materials.get_mut(material) {
+                    material.texture = None;
+                }
+            }
+            continue;
+        };
+
+        let tiling = TextureTiling {
+            shape: *shape,
+            tiles: transform.scale.truncate() / noise.tile_size.max(1.),
+        };
+        // Only rebuild the mesh when its shape or tiling changed, not on every edit of the solid.
+        if current_tiling != Some(&tiling) {
+            let mut mesh = shape.mesh([WHITE; 4]);
+            if let Some(VertexAttributeValues::Float32x2(uvs)) =
+                mesh.attribute_mut(Mesh::ATTRIBUTE_UV_0)
+            {
+                for uv in uvs {
+                    uv[0] *= tiling.tiles.x;
+                    uv[1] *= tiling.tiles.y;
+                }
+            }
+            commands
+                .entity(entity)
+                .insert((Mesh2d(mesh_assets.add(mesh)), tiling));
+        }
+        let stale = color_materials
+            .get(material)
+            .is_some_and(|material| material.texture.as_ref() != Some(image));
+        if stale {
+            if let Some(material) = color_materials.get_mut(material) {
+                material.texture = Some(image.clone());
+            }
+        }
+    }
+}
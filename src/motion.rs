@@ -0,0 +1,136 @@
+use std::f32::consts::TAU;
+use std::mem::{discriminant, Discriminant};
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::bodies::BodyType;
+use crate::{Modifying, Solid};
+
+/// Animation of a placed kinematic box, driven through its velocity so it pushes balls
+/// physically.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Motion {
+    #[default]
+    None,
+    /// Spins at a constant angular velocity in radians per second.
+    Spin { angular_velocity: f32 },
+    /// Moves back and forth between where it was placed and `offset` from there.
+    Shuttle { offset: Vec2, speed: f32 },
+    /// Swings sinusoidally around where it was placed.
+    Oscillate { amplitude: Vec2, period: f32 },
+}
+
+impl Motion {
+    pub fn label(&self) -> &str {
+        match self {
+            Motion::None => "None",
+            Motion::Spin { .. } => "Spin",
+            Motion::Shuttle { .. } => "Shuttle",
+            Motion::Oscillate { .. } => "Oscillate",
+        }
+    }
+
+    /// Offset from the origin `time` seconds after the motion started.
+    fn offset(&self, time: f32) -> Vec2 {
+        match *self {
+            Motion::None | Motion::Spin { .. } => Vec2::ZERO,
+            Motion::Shuttle { offset, speed } => {
+                let length = offset.length();
+                if length == 0. || speed <= 0. {
+                    return Vec2::ZERO;
+                }
+                // Triangle wave from 0 to 1 and back.
+                let phase = (time * speed / (2. * length)).fract();
+                offset * (1. - (phase * 2. - 1.).abs())
+            }
+            Motion::Oscillate { amplitude, period } => {
+                if period <= 0. {
+                    return Vec2::ZERO;
+                }
+                amplitude * (time / period * TAU).sin()
+            }
+        }
+    }
+}
+
+/// Where an animated solid was when its motion started. Kept across edits of the motion's
+/// settings, so changing its speed doesn't move the solid away from where it was placed.
+#[derive(Component, Debug)]
+pub struct MotionOrigin {
+    position: Vec2,
+    started: f32,
+    kind: Discriminant<Motion>,
+}
+
+/// Restarts the motion of solids that are being modified.
+pub fn reset_motion_origins(
+    mut commands: Commands,
+    query: Query<Entity, (With<MotionOrigin>, With<Modifying>)>,
+) {
+    for entity in &query {
+        commands.entity(entity).remove::<MotionOrigin>();
+    }
+}
+
+/// Sets the velocity of animated solids so they reach their next position by the next frame.
+pub fn animate_solids(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<
+        (
+            Entity,
+            &Solid,
+            &Transform,
+            &mut Velocity,
+            Option<&MotionOrigin>,
+        ),
+        Without<Modifying>,
+    >,
+) {
+    let dt = time.delta_secs();
+    let now = time.elapsed_secs();
+    for (entity, solid, transform, mut velocity, origin) in &mut query {
+        let Solid::Box {
+            body: BodyType::Kinematic,
+            motion,
+            ..
+        } = solid
+        else {
+            if origin.is_some() {
+                commands.entity(entity).remove::<MotionOrigin>();
+            }
+            continue;
+        };
+        if *motion == Motion::None {
+            if origin.is_some() {
+                commands.entity(entity).remove::<MotionOrigin>();
+                *velocity = Velocity::zero();
+            }
+            continue;
+        }
+        let position = transform.translation.truncate();
+        let kind = discriminant(motion);
+        let Some(origin) = origin.filter(|origin| origin.kind == kind) else {
+            // Anchor where the solid is now, stopping it until the motion starts next frame.
+            commands.entity(entity).insert(MotionOrigin {
+                position,
+                started: now,
+                kind,
+            });
+            *velocity = Velocity::zero();
+            continue;
+        };
+        if dt <= 0. {
+            continue;
+        }
+
+        let target = origin.position + motion.offset(now + dt - origin.started);
+        velocity.linvel = (target - position) / dt;
+        velocity.angvel = match motion {
+            Motion::Spin { angular_velocity } => *angular_velocity,
+            _ => 0.,
+        };
+    }
+}
@@ -1,25 +1,102 @@
+use std::f32::consts::{SQRT_2, TAU};
+
 use bevy::asset::RenderAssetUsages;
-use bevy::image::BevyDefault;
-use bevy::prelude::{default, Image};
+use bevy::image::{BevyDefault, ImageAddressMode, ImageSampler, ImageSamplerDescriptor};
+use bevy::prelude::{default, Color, FloatExt, Image, Vec2};
 use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
-use perlin_noise::PerlinNoise;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
 
 const TEXTURE_SIZE: u32 = 512;
+/// The noise repeats every this many cells, since the permutation has 256 entries.
+const MAX_PERIOD: i32 = 256;
+
+/// Seeded 2D gradient noise, so terrain and textures come out the same every time they are
+/// generated from the same settings.
+pub struct Perlin {
+    permutation: [u8; 512],
+}
 
-pub fn create_perlin_image() -> Image {
-    let perlin = PerlinNoise::new();
+impl Perlin {
+    pub fn new(seed: u32) -> Self {
+        let mut values: Vec<u8> = (0..=255).collect();
+        values.shuffle(&mut StdRng::seed_from_u64(seed as u64));
+        let mut permutation = [0; 512];
+        for (i, value) in permutation.iter_mut().enumerate() {
+            *value = values[i % 256];
+        }
+        Perlin { permutation }
+    }
+
+    fn gradient(&self, x: i32, y: i32, period: i32) -> Vec2 {
+        let (x, y) = (x.rem_euclid(period), y.rem_euclid(period));
+        let hash = self.permutation[self.permutation[x as usize] as usize + y as usize];
+        Vec2::from_angle(hash as f32 / 256. * TAU)
+    }
+
+    /// Noise at `point`, roughly from -1 to 1 and zero on integer coordinates.
+    pub fn noise(&self, point: Vec2) -> f32 {
+        self.tiled_noise(point, MAX_PERIOD)
+    }
+
+    /// Noise that repeats every `period` cells along both axes.
+    fn tiled_noise(&self, point: Vec2, period: i32) -> f32 {
+        let cell = point.floor();
+        let local = point - cell;
+        let (x, y) = (cell.x as i32, cell.y as i32);
+        let fade = local * local * local * (local * (local * 6. - 15.) + 10.);
+        let corner = |dx: i32, dy: i32| {
+            self.gradient(x + dx, y + dy, period)
+                .dot(local - Vec2::new(dx as f32, dy as f32))
+        };
+        let bottom = corner(0, 0).lerp(corner(1, 0), fade.x);
+        let top = corner(0, 1).lerp(corner(1, 1), fade.x);
+        bottom.lerp(top, fade.y) * SQRT_2
+    }
+
+    /// Sum of `octaves` layers of noise, each at twice the frequency and half the amplitude of
+    /// the one before, scaled back to roughly -1 to 1.
+    pub fn fractal(&self, point: Vec2, octaves: u32) -> f32 {
+        self.tiled_fractal(point, octaves, MAX_PERIOD)
+    }
+
+    /// Fractal noise that repeats every `period` cells along both axes.
+    pub fn tiled_fractal(&self, point: Vec2, octaves: u32, period: i32) -> f32 {
+        let period = period.clamp(1, MAX_PERIOD);
+        let mut sum = 0.;
+        let mut total = 0.;
+        let mut amplitude = 1.;
+        let mut frequency = 1;
+        for _ in 0..octaves.max(1) {
+            let octave_period = (period * frequency).min(MAX_PERIOD);
+            sum += self.tiled_noise(point * frequency as f32, octave_period) * amplitude;
+            total += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2;
+        }
+        sum / total
+    }
+}
+
+/// Opaque `tint` shaded by the noise, `scale` noise cells across. The scale is rounded to whole
+/// cells so the image tiles seamlessly.
+pub fn create_perlin_image(perlin: &Perlin, scale: f32, octaves: u32, tint: Color) -> Image {
+    let cells = (scale.round() as i32).max(1);
+    let tint = tint.to_srgba();
     let mut pixels = Vec::with_capacity((TEXTURE_SIZE * TEXTURE_SIZE * 4) as usize);
     for y in 0..TEXTURE_SIZE {
         for x in 0..TEXTURE_SIZE {
-            let noise_value = perlin.get2d([x as f64 / 64.0, y as f64 / 64.0]);
-            let alpha = (noise_value * 255.0) as u8;
-            pixels.push(255);
+            let point = Vec2::new(x as f32, y as f32) / TEXTURE_SIZE as f32 * cells as f32;
+            let noise_value = perlin.tiled_fractal(point, octaves, cells) * 0.5 + 0.5;
+            let shade = noise_value.clamp(0., 1.);
+            pixels.push((tint.red * shade * 255.0) as u8);
+            pixels.push((tint.green * shade * 255.0) as u8);
+            pixels.push((tint.blue * shade * 255.0) as u8);
             pixels.push(255);
-            pixels.push(255);
-            pixels.push(alpha);
         }
     }
-    Image::new(
+    let mut image = Image::new(
         Extent3d {
             width: TEXTURE_SIZE,
             height: TEXTURE_SIZE,
@@ -29,5 +106,54 @@ pub fn create_perlin_image() -> Image {
         pixels,
         TextureFormat::bevy_default(),
         RenderAssetUsages::RENDER_WORLD,
-    )
+    );
+    image.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
+        address_mode_u: ImageAddressMode::Repeat,
+        address_mode_v: ImageAddressMode::Repeat,
+        ..ImageSamplerDescriptor::linear()
+    });
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_points() -> impl Iterator<Item = Vec2> {
+        (0..40).flat_map(|x| (0..40).map(move |y| Vec2::new(x as f32, y as f32) * 0.37 - 3.))
+    }
+
+    #[test]
+    fn same_seed_gives_same_noise() {
+        let (first, second, other) = (Perlin::new(7), Perlin::new(7), Perlin::new(8));
+        for point in sample_points() {
+            assert_eq!(first.fractal(point, 4), second.fractal(point, 4));
+        }
+        assert!(sample_points().any(|point| first.noise(point) != other.noise(point)));
+    }
+
+    #[test]
+    fn tiled_fractal_repeats_every_period() {
+        let perlin = Perlin::new(3);
+        for period in [1, 4, 16] {
+            let shift = period as f32;
+            for point in sample_points() {
+                let value = perlin.tiled_fractal(point, 3, period);
+                let shifted_x = perlin.tiled_fractal(point + Vec2::new(shift, 0.), 3, period);
+                let shifted_y = perlin.tiled_fractal(point + Vec2::new(0., shift), 3, period);
+                assert!((value - shifted_x).abs() < 1e-4, "{point} period {period}");
+                assert!((value - shifted_y).abs() < 1e-4, "{point} period {period}");
+            }
+        }
+    }
+
+    #[test]
+    fn noise_stays_in_range() {
+        let perlin = Perlin::new(11);
+        for point in sample_points() {
+            assert!(perlin.noise(point).abs() <= 1. + 1e-5);
+            assert!(perlin.fractal(point, 6).abs() <= 1. + 1e-5);
+        }
+        assert_eq!(perlin.noise(Vec2::new(2., -5.)), 0.);
+    }
 }
@@ -0,0 +1,227 @@
+use std::f32::consts::TAU;
+
+use bevy::asset::RenderAssetUsages;
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy_egui::EguiContexts;
+use bevy_rapier2d::prelude::*;
+
+use crate::camera::is_panning;
+use crate::{Command, CommandEvent, Mode, Mouse, Solid};
+
+pub const POLYLINE_COLOR: Color = Color::srgb(0.8, 0.8, 0.7);
+/// Width of the stroke and its collider, in pixels.
+const STROKE_WIDTH: f32 = 6.;
+/// Segments of the round joints between stroke segments.
+const JOINT_SEGMENTS: u32 = 8;
+/// Distance the mouse has to move before another point is sampled.
+const SAMPLE_DISTANCE: f32 = 4.;
+/// How far simplification may move the path, in pixels.
+const SIMPLIFY_TOLERANCE: f32 = 2.;
+
+/// Points sampled from the mouse while a polyline is being drawn, in world space.
+#[derive(Component, Debug, Default)]
+pub struct DrawnPath(Vec<Vec2>);
+
+/// Scale of the solid that the stroke mesh and collider were last built for.
+#[derive(Component, Debug, PartialEq)]
+pub struct StrokeScale(Vec2);
+
+/// Drops points that are within `tolerance` of the line through their neighbours, using the
+/// Ramer-Douglas-Peucker algorithm.
+fn simplify(points: &[Vec2], tolerance: f32) -> Vec<Vec2> {
+    let (Some(&first), Some(&last)) = (points.first(), points.last()) else {
+        return Vec::new();
+    };
+    let distance = |point: Vec2| {
+        let direction = (last - first).normalize_or_zero();
+        if direction == Vec2::ZERO {
+            point.distance(first)
+        } else {
+            direction.perp_dot(point - first).abs()
+        }
+    };
+    let farthest = points
+        .get(1..points.len() - 1)
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+        .map(|(i, point)| (i + 1, distance(*point)))
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+    match farthest {
+        Some((index, distance)) if distance > tolerance => {
+            let mut simplified = simplify(&points[..=index], tolerance);
+            simplified.pop();
+            simplified.extend(simplify(&points[index..], tolerance));
+            simplified
+        }
+        _ if first == last => vec![first],
+        _ => vec![first, last],
+    }
+}
+
+/// Samples the mouse while the left button is held and turns the path into a polyline solid
+/// when it is released.
+pub fn draw_polylines(
+    mut commands: Commands,
+    mode: Res<Mode>,
+    mouse: Res<Mouse>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut query: Query<(Entity, &mut DrawnPath, &mut Solid, &mut Transform)>,
+    mut command_sender: EventWriter<CommandEvent>,
+    mut gizmos: Gizmos,
+    mut egui_contexts: EguiContexts,
+) {
+    if *mode != Mode::Draw {
+        return;
+    }
+    let can_start = !egui_contexts.ctx_mut().is_pointer_over_area()
+        && !is_panning(&mouse_input, &keyboard_input);
+    for (entity, mut path, mut solid, mut transform) in &mut query {
+        if mouse_input.just_pressed(MouseButton::Left) && can_start {
+            path.0 = vec![mouse.snapped];
+        } else if mouse_input.pressed(MouseButton::Left) {
+            if path
+                .0
+                .last()
+                .is_some_and(|last| last.distance(mouse.snapped) >= SAMPLE_DISTANCE)
+            {
+                path.0.push(mouse.snapped);
+            }
+        } else if mouse_input.just_released(MouseButton::Left) {
+            let points = simplify(&path.0, SIMPLIFY_TOLERANCE);
+            path.0.clear();
+            if points.len() < 2 {
+                continue;
+            }
+            let bounds = Rect::from_corners(points[0], points[0]);
+            let bounds = points
+                .iter()
+                .fold(bounds, |bounds, point| bounds.union_point(*point));
+            // Straight lines still get some thickness, so the solid never has a zero scale.
+            let size = bounds.size().max(Vec2::splat(STROKE_WIDTH));
+            *solid = Solid::Polyline {
+                points: points
+                    .iter()
+                    .map(|point| (*point - bounds.center()) / size)
+                    .collect(),
+            };
+            transform.translation = bounds.center().extend(transform.translation.z);
+            transform.scale = size.extend(1.);
+            commands.entity(entity).remove::<DrawnPath>();
+            command_sender.send(CommandEvent {
+                command: Command::Scaled,
+            });
+        }
+        gizmos.linestrip_2d(path.0.iter().copied(), POLYLINE_COLOR);
+    }
+}
+
+/// Rebuilds the stroke mesh of polylines whenever their points or scale change, and their
+/// collider too once they take part in the simulation. The stroke keeps its width no matter
+/// how the solid is scaled.
+pub fn sync_polylines(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    query: Query<(
+        Entity,
+        Ref<Solid>,
+        &Transform,
+        Option<&StrokeScale>,
+        Has<RigidBody>,
+        Has<Collider>,
+    )>,
+) {
+    for (entity, solid, transform, built, has_body, has_collider) in &query {
+        let Solid::Polyline { points } = &*solid else {
+            continue;
+        };
+        if points.len() < 2 {
+            continue;
+        }
+        let scale = transform.scale.truncate();
+        let stale = solid.is_changed() || built != Some(&StrokeScale(scale));
+        if !stale && (has_collider || !has_body) {
+            continue;
+        }
+        let points: Vec<Vec2> = points.iter().map(|point| *point * scale).collect();
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.insert((
+            Mesh2d(meshes.add(stroke_mesh(&points, scale))),
+            StrokeScale(scale),
+        ));
+        if has_body {
+            entity_commands.insert(stroke_collider(&points));
+        }
+    }
+}
+
+/// Capsules along each segment, in pixels since the collider ignores the solid's scale.
+fn stroke_collider(points: &[Vec2]) -> Collider {
+    Collider::compound(
+        points
+            .windows(2)
+            .map(|segment| {
+                (
+                    Vec2::ZERO,
+                    0.,
+                    Collider::capsule(segment[0], segment[1], STROKE_WIDTH / 2.),
+                )
+            })
+            .collect(),
+    )
+}
+
+/// A quad along each segment with a round joint at each point, built in pixels and divided by
+/// `scale` to fit the solid's transform.
+fn stroke_mesh(points: &[Vec2], scale: Vec2) -> Mesh {
+    // A zero scale, from the inspector for example, would fill the mesh with NaNs.
+    let scale = scale.max(Vec2::splat(f32::EPSILON));
+    let radius = STROKE_WIDTH / 2.;
+    let mut positions: Vec<Vec2> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+    for segment in points.windows(2) {
+        let normal = (segment[1] - segment[0]).normalize_or_zero().perp() * radius;
+        let start = positions.len() as u32;
+        positions.extend([
+            segment[0] - normal,
+            segment[1] - normal,
+            segment[1] + normal,
+            segment[0] + normal,
+        ]);
+        indices.extend([start, start + 1, start + 2, start, start + 2, start + 3]);
+    }
+    for point in points {
+        let center = positions.len() as u32;
+        positions.push(*point);
+        positions.extend(
+            (0..JOINT_SEGMENTS).map(|i| {
+                *point + Vec2::from_angle(i as f32 / JOINT_SEGMENTS as f32 * TAU) * radius
+            }),
+        );
+        indices.extend((0..JOINT_SEGMENTS).flat_map(|i| {
+            [
+                center,
+                center + 1 + i,
+                center + 1 + (i + 1) % JOINT_SEGMENTS,
+            ]
+        }));
+    }
+
+    let vertex_count = positions.len();
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        positions
+            .iter()
+            .map(|position| (*position / scale).extend(0.).to_array())
+            .collect::<Vec<_>>(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0., 0., 1.]; vertex_count])
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0., 0.]; vertex_count])
+    .with_inserted_indices(Indices::U32(indices))
+}
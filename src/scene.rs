@@ -0,0 +1,439 @@
+use std::error::Error;
+use std::fs;
+
+use bevy::prelude::*;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::balls::Ball;
+use crate::counters::CounterState;
+use crate::history::History;
+use crate::joints::{spawn_joint, spawn_world_anchor, Joint, JointKind, WorldAnchor};
+use crate::materials::MaterialLibrary;
+use crate::textures::{MeshVariant, Meshes};
+use crate::world::WorldBounds;
+use crate::{insert_physics, Hoverable, Mode, Modifying, OriginalColor, Solid, ZCounter};
+
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub enum SceneEvent {
+    Save,
+    Load,
+}
+
+/// Path of the scene file used by the save and load commands.
+#[derive(Resource, Debug)]
+pub struct SceneFile(pub String);
+
+impl Default for SceneFile {
+    fn default() -> Self {
+        SceneFile("scene.ron".to_string())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Scene {
+    pub solids: Vec<SceneSolid>,
+    #[serde(default)]
+    pub world: WorldBounds,
+    #[serde(default)]
+    pub materials: MaterialLibrary,
+    #[serde(default)]
+    pub joints: Vec<SceneJoint>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SceneSolid {
+    pub solid: Solid,
+    pub transform: Transform,
+    pub mesh: Option<MeshVariant>,
+    pub color: Color,
+}
+
+impl SceneSolid {
+    pub fn from_components(
+        solid: &Solid,
+        transform: &Transform,
+        mesh: Option<&MeshVariant>,
+        color: Option<&OriginalColor>,
+    ) -> Self {
+        SceneSolid {
+            solid: solid.clone(),
+            transform: *transform,
+            mesh: mesh.copied(),
+            color: color.map(|c| c.0).unwrap_or(Color::WHITE),
+        }
+    }
+
+    pub fn spawn(
+        &self,
+        commands: &mut Commands,
+        meshes: &Meshes,
+        materials: &mut Assets<ColorMaterial>,
+    ) -> Entity {
+        let mut entity_commands = commands.spawn((
+            self.solid.clone(),
+            Hoverable::default(),
+            OriginalColor(self.color),
+            self.transform,
+        ));
+
+        match self.solid {
+            Solid::Box { shape, .. } => {
+                let variant = self.mesh.unwrap_or(MeshVariant(0));
+                entity_commands.insert((
+                    variant,
+                    meshes.get(shape, variant),
+                    MeshMaterial2d(materials.add(ColorMaterial::from_color(self.color))),
+                ));
+            }
+            Solid::Polyline { .. } | Solid::Terrain(_) => {
+                entity_commands.insert(MeshMaterial2d(
+                    materials.add(ColorMaterial::from_color(self.color)),
+                ));
+            }
+            Solid::ForceField { .. } | Solid::Emitter(_) => {
+                entity_commands.insert(Sprite {
+                    color: self.color,
+                    ..default()
+                });
+            }
+            Solid::Counter(_) => {
+                entity_commands.insert((
+                    Sprite {
+                        color: self.color,
+                        ..default()
+                    },
+                    CounterState::default(),
+                ));
+            }
+        }
+
+        insert_physics(&mut entity_commands, &self.solid);
+        entity_commands.id()
+    }
+}
+
+/// What a saved joint is attached to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum SceneJointBody<T> {
+    Solid(T),
+    /// A world anchor at this position.
+    World(Vec2),
+}
+
+/// A joint that outlives the entities it was attached to. Solids are referred to by index into
+/// the saved solids in scenes and the clipboard, and by entity in the history, which remaps them
+/// when solids are respawned.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct SceneJoint<T = usize> {
+    pub kind: JointKind,
+    pub bodies: [SceneJointBody<T>; 2],
+    pub anchors: [Vec2; 2],
+}
+
+impl<T> SceneJoint<T> {
+    /// Converts the solids the joint is attached to, or returns `None` if any can't be.
+    pub fn map<U>(&self, mut f: impl FnMut(&T) -> Option<U>) -> Option<SceneJoint<U>> {
+        let mut body = |body: &SceneJointBody<T>| match body {
+            SceneJointBody::Solid(solid) => f(solid).map(SceneJointBody::Solid),
+            SceneJointBody::World(point) => Some(SceneJointBody::World(*point)),
+        };
+        Some(SceneJoint {
+            kind: self.kind,
+            bodies: [body(&self.bodies[0])?, body(&self.bodies[1])?],
+            anchors: self.anchors,
+        })
+    }
+
+    /// Moves the world anchors by `offset`.
+    pub fn offset(mut self, offset: Vec2) -> Self {
+        for body in &mut self.bodies {
+            if let SceneJointBody::World(point) = body {
+                *point += offset;
+            }
+        }
+        self
+    }
+}
+
+impl SceneJoint<Entity> {
+    pub fn from_joint(joint: &Joint, anchors: &Query<&Transform, With<WorldAnchor>>) -> Self {
+        let body = |entity: Entity| match anchors.get(entity) {
+            Ok(transform) => SceneJointBody::World(transform.translation.truncate()),
+            Err(_) => SceneJointBody::Solid(entity),
+        };
+        SceneJoint {
+            kind: joint.kind,
+            bodies: joint.bodies.map(body),
+            anchors: joint.anchors,
+        }
+    }
+
+    /// Spawns the joint along with its world anchors.
+    pub fn spawn(&self, commands: &mut Commands) -> Entity {
+        let bodies = self.bodies.map(|body| match body {
+            SceneJointBody::Solid(entity) => entity,
+            SceneJointBody::World(point) => spawn_world_anchor(commands, point),
+        });
+        spawn_joint(
+            commands,
+            Joint {
+                kind: self.kind,
+                bodies,
+                anchors: self.anchors,
+            },
+        )
+    }
+}
+
+/// Joints among `entities`, referring to them by their index.
+pub fn joints_between(
+    entities: &[Entity],
+    joints: &Query<&Joint>,
+    anchors: &Query<&Transform, With<WorldAnchor>>,
+) -> Vec<SceneJoint> {
+    joints
+        .iter()
+        .filter_map(|joint| {
+            SceneJoint::from_joint(joint, anchors)
+                .map(|entity| entities.iter().position(|other| other == entity))
+        })
+        .collect()
+}
+
+fn save_scene(scene: &Scene, path: &str) -> Result<(), Box<dyn Error>> {
+    let contents = ron::ser::to_string_pretty(scene, PrettyConfig::default())?;
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+fn load_scene(path: &str) -> Result<Scene, Box<dyn Error>> {
+    let contents = fs::read_to_string(path)?;
+    Ok(ron::from_str(&migrate_unit_boxes(&contents))?)
+}
+
+/// Scenes saved before boxes had settings write them as a bare `Box`, which RON can't read as the
+/// struct variant it is now. Gives those an empty field list so they get the default settings.
+fn migrate_unit_boxes(contents: &str) -> String {
+    let mut migrated = String::with_capacity(contents.len());
+    let mut rest = contents;
+    while let Some(index) = rest.find("solid:") {
+        let (before, after) = rest.split_at(index + "solid:".len());
+        migrated.push_str(before);
+        let value = after.trim_start();
+        migrated.push_str(&after[..after.len() - value.len()]);
+        rest = value;
+        if let Some(tail) = value.strip_prefix("Box") {
+            if !tail.trim_start().starts_with('(') {
+                migrated.push_str("Box()");
+                rest = tail;
+            }
+        }
+    }
+    migrated.push_str(rest);
+    migrated
+}
+
+pub fn handle_scene_events(
+    mut event_reader: EventReader<SceneEvent>,
+    mut commands: Commands,
+    mode: Res<Mode>,
+    scene_file: Res<SceneFile>,
+    meshes: Res<Meshes>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut z_counter: ResMut<ZCounter>,
+    mut history: ResMut<History>,
+    mut world_bounds: ResMut<WorldBounds>,
+    mut material_library: ResMut<MaterialLibrary>,
+    solids: Query<
+        (
+            Entity,
+            &Solid,
+            &Transform,
+            Option<&MeshVariant>,
+            Option<&OriginalColor>,
+        ),
+        Without<Modifying>,
+    >,
+    balls: Query<Entity, With<Ball>>,
+    joints: Query<&Joint>,
+    anchors: Query<&Transform, With<WorldAnchor>>,
+) {
+    for event in event_reader.read() {
+        if *mode != Mode::Default {
+            continue;
+        }
+        match event {
+            SceneEvent::Save => {
+                let entities: Vec<Entity> = solids.iter().map(|(entity, ..)| entity).collect();
+                let scene = Scene {
+                    solids: solids
+                        .iter()
+                        .map(|(_, solid, transform, mesh, color)| {
+                            SceneSolid::from_components(solid, transform, mesh, color)
+                        })
+                        .collect(),
+                    world: *world_bounds,
+                    materials: material_library.clone(),
+                    joints: joints_between(&entities, &joints, &anchors),
+                };
+                match save_scene(&scene, &scene_file.0) {
+                    Ok(()) => info!("Saved {} solids to {}", scene.solids.len(), scene_file.0),
+                    Err(error) => error!("Failed to save scene to {}: {error}", scene_file.0),
+                }
+            }
+            SceneEvent::Load => match load_scene(&scene_file.0) {
+                Ok(scene) => {
+                    for (entity, ..) in &solids {
+                        commands.entity(entity).despawn();
+                    }
+                    for entity in &balls {
+                        commands.entity(entity).despawn();
+                    }
+                    history.clear();
+                    *world_bounds = scene.world;
+                    *material_library = scene.materials.clone();
+                    z_counter.0 = 0.;
+                    let mut spawned = Vec::new();
+                    for solid in &scene.solids {
+                        spawned.push(solid.spawn(&mut commands, &meshes, &mut materials));
+                        z_counter.0 = z_counter.0.max(solid.transform.translation.z + 0.01);
+                    }
+                    for joint in &scene.joints {
+                        if let Some(joint) = joint.map(|index| spawned.get(*index).copied()) {
+                            joint.spawn(&mut commands);
+                        }
+                    }
+                    info!("Loaded {} solids from {}", scene.solids.len(), scene_file.0);
+                }
+                Err(error) => error!("Failed to load scene from {}: {error}", scene_file.0),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+    use crate::bodies::BodyType;
+    use crate::emitters::EmitterSettings;
+    use crate::force_fields::FieldKind;
+    use crate::motion::Motion;
+    use crate::shapes::SolidShape;
+    use crate::world::{EdgeBehaviour, Edges};
+
+    const UNIT_BOX_SCENE: &str = "(
+    solids: [
+        (
+            solid: Box,
+            transform: (
+                translation: (10.0, -20.0, 0.5),
+                rotation: (0.0, 0.0, 0.0, 1.0),
+                scale: (100.0, 50.0, 1.0),
+            ),
+            mesh: None,
+            color: Srgba((red: 1.0, green: 0.5, blue: 0.25, alpha: 1.0)),
+        ),
+    ],
+)";
+
+    #[test]
+    fn scene_round_trips_through_ron() {
+        let solid = |solid, translation| SceneSolid {
+            solid,
+            transform: Transform::from_translation(translation)
+                .with_rotation(Quat::from_rotation_z(0.3))
+                .with_scale(Vec3::new(120., 40., 1.)),
+            mesh: None,
+            color: Color::srgb(0.2, 0.4, 0.6),
+        };
+        let scene = Scene {
+            solids: vec![
+                solid(
+                    Solid::Box {
+                        shape: SolidShape::Polygon { sides: 6 },
+                        body: BodyType::Dynamic,
+                        density: 2.5,
+                        motion: Motion::Spin {
+                            angular_velocity: 1.5,
+                        },
+                        material: Some(1),
+                    },
+                    Vec3::new(-100., 50., 0.1),
+                ),
+                solid(
+                    Solid::ForceField {
+                        direction: 1.2,
+                        magnitude: 30.,
+                        falloff: 0.25,
+                        kind: FieldKind::Vortex { strength: -5. },
+                    },
+                    Vec3::new(80., -20., 0.2),
+                ),
+                solid(
+                    Solid::Emitter(EmitterSettings::default()),
+                    Vec3::new(0., 300., 0.3),
+                ),
+            ],
+            world: WorldBounds {
+                width: 900.,
+                height: 600.,
+                edges: Edges {
+                    top: EdgeBehaviour::Wrap,
+                    bottom: EdgeBehaviour::Wall,
+                    ..default()
+                },
+            },
+            materials: MaterialLibrary::default(),
+            joints: vec![SceneJoint {
+                kind: JointKind::Rope { length: 150. },
+                bodies: [
+                    SceneJointBody::Solid(0),
+                    SceneJointBody::World(Vec2::new(-100., 200.)),
+                ],
+                anchors: [Vec2::new(0.5, 0.), Vec2::ZERO],
+            }],
+        };
+
+        let saved = ron::ser::to_string_pretty(&scene, PrettyConfig::default()).unwrap();
+        let loaded: Scene = ron::from_str(&migrate_unit_boxes(&saved)).unwrap();
+        assert_eq!(loaded, scene);
+    }
+
+    #[test]
+    fn unit_boxes_load_with_default_settings_and_round_trip() {
+        let scene: Scene = ron::from_str(&migrate_unit_boxes(UNIT_BOX_SCENE)).unwrap();
+        let [solid] = scene.solids.as_slice() else {
+            panic!("expected one solid, got {:?}", scene.solids);
+        };
+        let Solid::Box { body, density, .. } = &solid.solid else {
+            panic!("expected a box, got {:?}", solid.solid);
+        };
+        assert_eq!(*body, BodyType::Kinematic);
+        assert_eq!(*density, 1.);
+        assert_eq!(solid.transform.translation, Vec3::new(10., -20., 0.5));
+
+        let saved = ron::ser::to_string_pretty(&scene, PrettyConfig::default()).unwrap();
+        let reloaded: Scene = ron::from_str(&migrate_unit_boxes(&saved)).unwrap();
+        assert_eq!(reloaded.solids, scene.solids);
+    }
+
+    #[test]
+    fn force_field_force_is_split_into_direction_and_magnitude() {
+        let solid: Solid = ron::from_str("ForceField(force: (0.0, -2.0), falloff: 0.5)").unwrap();
+        let Solid::ForceField {
+            direction,
+            magnitude,
+            falloff,
+            ..
+        } = solid
+        else {
+            panic!("expected a force field, got {solid:?}");
+        };
+        assert!((direction + FRAC_PI_2).abs() < 1e-6);
+        assert!((magnitude - 2.).abs() < 1e-6);
+        assert_eq!(falloff, 0.5);
+    }
+}
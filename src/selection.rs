@@ -0,0 +1,140 @@
+use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+
+use crate::camera::is_panning;
+use crate::{Hoverable, Mode, Mouse, Solid};
+
+const SELECTION_COLOR: Color = Color::srgb(1.0, 0.8, 0.0);
+
+/// Marks the solids that group commands apply to. The inspector shows every selected solid.
+#[derive(Component)]
+pub struct Selected;
+
+#[derive(Event, Debug, Clone, PartialEq)]
+pub enum SelectionEvent {
+    /// Selects exactly these entities, clearing the selection when empty.
+    Replace(Vec<Entity>),
+    Add(Vec<Entity>),
+    Toggle(Entity),
+}
+
+/// World position where the current rubber band drag started.
+#[derive(Resource, Debug, Default)]
+pub struct RubberBand {
+    start: Option<Vec2>,
+}
+
+pub fn shift_pressed(keyboard_input: &ButtonInput<KeyCode>) -> bool {
+    keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])
+}
+
+pub fn handle_selection_events(
+    mut event_reader: EventReader<SelectionEvent>,
+    mut commands: Commands,
+    selected: Query<Entity, With<Selected>>,
+) {
+    for event in event_reader.read() {
+        match event {
+            SelectionEvent::Replace(entities) => {
+                for entity in &selected {
+                    if !entities.contains(&entity) {
+                        commands.entity(entity).remove::<Selected>();
+                    }
+                }
+                for entity in entities {
+                    commands.entity(*entity).insert(Selected);
+                }
+            }
+            SelectionEvent::Add(entities) => {
+                for entity in entities {
+                    commands.entity(*entity).insert(Selected);
+                }
+            }
+            SelectionEvent::Toggle(entity) => {
+                if selected.contains(*entity) {
+                    commands.entity(*entity).remove::<Selected>();
+                } else {
+                    commands.entity(*entity).insert(Selected);
+                }
+            }
+        }
+    }
+}
+
+/// Axis-aligned bounds of a solid's rotated unit square.
+pub fn solid_bounds(transform: &GlobalTransform) -> Rect {
+    let (scale, rotation, translation) = transform.to_scale_rotation_translation();
+    let half_x = (rotation * Vec3::X * scale.x * 0.5).truncate().abs();
+    let half_y = (rotation * Vec3::Y * scale.y * 0.5).truncate().abs();
+    Rect::from_center_half_size(translation.truncate(), half_x + half_y)
+}
+
+/// Dragging from empty space selects every solid the band touches, adding to the selection
+/// while Shift is held. A click without dragging clears the selection.
+pub fn update_rubber_band(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mode: Res<Mode>,
+    mouse: Res<Mouse>,
+    mut rubber_band: ResMut<RubberBand>,
+    mut event_writer: EventWriter<SelectionEvent>,
+    hoverables: Query<&Hoverable>,
+    solids: Query<(Entity, &GlobalTransform), With<Solid>>,
+    mut gizmos: Gizmos,
+    mut egui_contexts: EguiContexts,
+) {
+    if *mode != Mode::Default {
+        rubber_band.start = None;
+        return;
+    }
+    if mouse_input.just_pressed(MouseButton::Left)
+        && !is_panning(&mouse_input, &keyboard_input)
+        && !egui_contexts.ctx_mut().is_pointer_over_area()
+        && hoverables
+            .iter()
+            .all(|hoverable| hoverable.position.is_none())
+    {
+        rubber_band.start = Some(mouse.position);
+    }
+    let Some(start) = rubber_band.start else {
+        return;
+    };
+    let band = Rect::from_corners(start, mouse.position);
+
+    if mouse_input.pressed(MouseButton::Left) {
+        gizmos.rect_2d(band.center(), band.size(), SELECTION_COLOR);
+        return;
+    }
+
+    rubber_band.start = None;
+    let shift = shift_pressed(&keyboard_input);
+    let entities: Vec<Entity> = if band.size().max_element() < 2. {
+        Vec::new()
+    } else {
+        solids
+            .iter()
+            .filter(|(_, transform)| !solid_bounds(transform).intersect(band).is_empty())
+            .map(|(entity, _)| entity)
+            .collect()
+    };
+    if shift {
+        event_writer.send(SelectionEvent::Add(entities));
+    } else {
+        event_writer.send(SelectionEvent::Replace(entities));
+    }
+}
+
+pub fn draw_selection(query: Query<&GlobalTransform, With<Selected>>, mut gizmos: Gizmos) {
+    for transform in &query {
+        let (scale, rotation, translation) = transform.to_scale_rotation_translation();
+        let isometry = Isometry2d::new(
+            translation.truncate(),
+            Rot2::radians(rotation.to_euler(EulerRot::ZYX).0),
+        );
+        gizmos.rect_2d(
+            isometry,
+            scale.truncate() + Vec2::splat(6.),
+            SELECTION_COLOR,
+        );
+    }
+}
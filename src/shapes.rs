@@ -0,0 +1,194 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::asset::RenderAssetUsages;
+use bevy::color::{ColorToComponents, Srgba};
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::HoverPosition;
+
+/// Sides of the polygons that meshes are generated for up front.
+pub const POLYGON_SIDES: std::ops::RangeInclusive<u32> = 5..=12;
+const CIRCLE_SEGMENTS: u32 = 32;
+/// How close to the outline, in the shape's unit square, counts as hovering an edge.
+const EDGE_DISTANCE: f32 = 0.05;
+/// How close to a handle, in the shape's unit square, counts as hovering a corner.
+const HANDLE_DISTANCE: f32 = 0.1;
+
+/// Outline of a solid body, fitted to the unit square that the solid's transform scales.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SolidShape {
+    #[default]
+    Rectangle,
+    Circle,
+    /// Round caps when the solid is twice as wide as it is tall.
+    Capsule,
+    Triangle,
+    /// A regular polygon, limited to `POLYGON_SIDES`.
+    Polygon {
+        #[serde(deserialize_with = "deserialize_sides")]
+        sides: u32,
+    },
+}
+
+/// Clamps the sides of loaded polygons to `POLYGON_SIDES`, so their mesh matches their collider.
+fn deserialize_sides<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    let sides = u32::deserialize(deserializer)?;
+    Ok(sides.clamp(*POLYGON_SIDES.start(), *POLYGON_SIDES.end()))
+}
+
+impl SolidShape {
+    pub fn all() -> impl Iterator<Item = SolidShape> {
+        [
+            SolidShape::Rectangle,
+            SolidShape::Circle,
+            SolidShape::Capsule,
+            SolidShape::Triangle,
+        ]
+        .into_iter()
+        .chain(POLYGON_SIDES.map(|sides| SolidShape::Polygon { sides }))
+    }
+
+    pub fn label(&self) -> &str {
+        match self {
+            SolidShape::Rectangle => "Box",
+            SolidShape::Circle => "Circle",
+            SolidShape::Capsule => "Capsule",
+            SolidShape::Triangle => "Triangle",
+            SolidShape::Polygon { .. } => "Polygon",
+        }
+    }
+
+    /// Convex outline in counter-clockwise order.
+    pub fn outline(&self) -> Vec<Vec2> {
+        match *self {
+            SolidShape::Rectangle => vec![
+                Vec2::new(-0.5, -0.5),
+                Vec2::new(0.5, -0.5),
+                Vec2::new(0.5, 0.5),
+                Vec2::new(-0.5, 0.5),
+            ],
+            SolidShape::Circle => (0..CIRCLE_SEGMENTS)
+                .map(|i| Vec2::from_angle(i as f32 / CIRCLE_SEGMENTS as f32 * TAU) * 0.5)
+                .collect(),
+            SolidShape::Capsule => {
+                let half = CIRCLE_SEGMENTS / 2;
+                let cap = |center: f32, start: f32| {
+                    (0..=half).map(move |i| {
+                        let direction = Vec2::from_angle(start + i as f32 / half as f32 * PI);
+                        Vec2::new(center + direction.x * 0.25, direction.y * 0.5)
+                    })
+                };
+                cap(0.25, -PI / 2.).chain(cap(-0.25, PI / 2.)).collect()
+            }
+            SolidShape::Triangle => vec![
+                Vec2::new(-0.5, -0.5),
+                Vec2::new(0.5, -0.5),
+                Vec2::new(0., 0.5),
+            ],
+            SolidShape::Polygon { sides } => {
+                let sides = sides.clamp(*POLYGON_SIDES.start(), *POLYGON_SIDES.end());
+                (0..sides)
+                    .map(|i| Vec2::from_angle(PI / 2. + i as f32 / sides as f32 * TAU) * 0.5)
+                    .collect()
+            }
+        }
+    }
+
+    /// Points that resize the solid when dragged.
+    fn handles(&self) -> Vec<Vec2> {
+        match self {
+            SolidShape::Circle => [1., 3., 5., 7.]
+                .map(|eighth| Vec2::from_angle(eighth * PI / 4.) * 0.5)
+                .to_vec(),
+            SolidShape::Capsule => vec![
+                Vec2::new(-0.25, -0.5),
+                Vec2::new(0.25, -0.5),
+                Vec2::new(0.25, 0.5),
+                Vec2::new(-0.25, 0.5),
+            ],
+            _ => self.outline(),
+        }
+    }
+
+    /// Signed distance from `local` to the outline, positive inside.
+    fn edge_distance(&self, local: Vec2) -> f32 {
+        let outline = self.outline();
+        outline
+            .iter()
+            .zip(outline.iter().cycle().skip(1))
+            .map(|(a, b)| (*b - *a).normalize_or_zero().perp_dot(local - *a))
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    /// Classifies a point in the unit square for moving, rotating or resizing the solid.
+    pub fn hover_position(&self, local: Vec2) -> HoverPosition {
+        if self
+            .handles()
+            .iter()
+            .any(|handle| handle.distance(local) < HANDLE_DISTANCE)
+        {
+            HoverPosition::Corner
+        } else if self.edge_distance(local) < EDGE_DISTANCE {
+            HoverPosition::Edge
+        } else {
+            HoverPosition::Center
+        }
+    }
+
+    pub fn collider(&self) -> Collider {
+        match self {
+            SolidShape::Rectangle => Collider::cuboid(0.5, 0.5),
+            SolidShape::Circle => Collider::ball(0.5),
+            _ => {
+                Collider::convex_hull(&self.outline()).unwrap_or_else(|| Collider::cuboid(0.5, 0.5))
+            }
+        }
+    }
+
+    /// Triangle fan over the outline, with vertex colors blended from `corners`, given as
+    /// top right, top left, bottom left and bottom right like `Rectangle` meshes.
+    pub fn mesh(&self, corners: [Srgba; 4]) -> Mesh {
+        let [top_right, top_left, bottom_left, bottom_right] = corners.map(Srgba::to_vec4);
+        let color = |point: Vec2| {
+            let u = point.x + 0.5;
+            let v = point.y + 0.5;
+            let top = top_left.lerp(top_right, u);
+            let bottom = bottom_left.lerp(bottom_right, u);
+            bottom.lerp(top, v).to_array()
+        };
+
+        let points: Vec<Vec2> = std::iter::once(Vec2::ZERO).chain(self.outline()).collect();
+        let count = points.len() as u32 - 1;
+        let indices = (0..count)
+            .flat_map(|i| [0, i + 1, (i + 1) % count + 1])
+            .collect();
+
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            points
+                .iter()
+                .map(|point| [point.x, point.y, 0.])
+                .collect::<Vec<_>>(),
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0., 0., 1.]; points.len()])
+        .with_inserted_attribute(
+            Mesh::ATTRIBUTE_UV_0,
+            points
+                .iter()
+                .map(|point| [point.x + 0.5, 0.5 - point.y])
+                .collect::<Vec<_>>(),
+        )
+        .with_inserted_attribute(
+            Mesh::ATTRIBUTE_COLOR,
+            points.iter().map(|point| color(*point)).collect::<Vec<_>>(),
+        )
+        .with_inserted_indices(Indices::U32(indices))
+    }
+}
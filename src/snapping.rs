@@ -0,0 +1,165 @@
+use bevy::prelude::*;
+
+use crate::selection::solid_bounds;
+use crate::world::WorldBounds;
+use crate::{Modifying, Mouse, Solid};
+
+/// Most grid lines drawn along each axis, so tiny grids stay cheap to draw.
+const MAX_GRID_LINES: f32 = 400.;
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct Snapping {
+    /// Snap positions and box corners to the grid and nearby edges.
+    pub enabled: bool,
+    pub grid: f32,
+    pub snap_to_edges: bool,
+    /// How close an edge of another solid has to be to win over the grid.
+    pub edge_distance: f32,
+    /// Rotation increment while Shift is held, in degrees.
+    pub angle_step: f32,
+}
+
+impl Default for Snapping {
+    fn default() -> Self {
+        Snapping {
+            enabled: false,
+            grid: 20.,
+            snap_to_edges: true,
+            edge_distance: 8.,
+            angle_step: 15.,
+        }
+    }
+}
+
+/// Edges of the solids that are not being modified, as x and y coordinates.
+#[derive(Resource, Debug, Default)]
+pub struct SnapTargets {
+    xs: Vec<f32>,
+    ys: Vec<f32>,
+}
+
+impl Snapping {
+    pub fn snap(&self, point: Vec2, targets: &SnapTargets) -> Vec2 {
+        if !self.enabled {
+            return point;
+        }
+        Vec2::new(
+            self.snap_axis(point.x, &targets.xs),
+            self.snap_axis(point.y, &targets.ys),
+        )
+    }
+
+    /// Snaps the center of a solid being moved so that its own edges, `half_size` away from the
+    /// center, line up with nearby edges. Without an edge close enough the center goes to the grid.
+    pub fn snap_moved(&self, center: Vec2, half_size: Vec2, targets: &SnapTargets) -> Vec2 {
+        if !self.enabled {
+            return center;
+        }
+        Vec2::new(
+            self.snap_span(center.x, half_size.x, &targets.xs),
+            self.snap_span(center.y, half_size.y, &targets.ys),
+        )
+    }
+
+    fn snap_axis(&self, value: f32, edges: &[f32]) -> f32 {
+        self.snap_span(value, 0., edges)
+    }
+
+    /// Snaps the span `center ± half` along one axis, moving it by the smallest shift that puts
+    /// either end on an edge.
+    fn snap_span(&self, center: f32, half: f32, edges: &[f32]) -> f32 {
+        if self.snap_to_edges {
+            let shift = [center - half, center + half]
+                .into_iter()
+                .flat_map(|end| edges.iter().map(move |edge| edge - end))
+                .min_by(|a, b| a.abs().total_cmp(&b.abs()));
+            if let Some(shift) = shift.filter(|shift| shift.abs() <= self.edge_distance) {
+                return center + shift;
+            }
+        }
+        self.snap_to_grid(center)
+    }
+
+    fn snap_to_grid(&self, value: f32) -> f32 {
+        if self.grid > 0. {
+            (value / self.grid).round() * self.grid
+        } else {
+            value
+        }
+    }
+
+    /// Rounds an angle in radians to the configured increment.
+    pub fn snap_angle(&self, angle: f32) -> f32 {
+        let step = self.angle_step.to_radians();
+        if step > 0. {
+            (angle / step).round() * step
+        } else {
+            angle
+        }
+    }
+}
+
+pub fn collect_snap_targets(
+    snapping: Res<Snapping>,
+    mut targets: ResMut<SnapTargets>,
+    solids: Query<&GlobalTransform, (With<Solid>, Without<Modifying>)>,
+) {
+    targets.xs.clear();
+    targets.ys.clear();
+    if !snapping.enabled || !snapping.snap_to_edges {
+        return;
+    }
+    for transform in &solids {
+        let bounds = solid_bounds(transform);
+        targets.xs.extend([bounds.min.x, bounds.max.x]);
+        targets.ys.extend([bounds.min.y, bounds.max.y]);
+    }
+}
+
+pub fn snap_mouse_position(
+    snapping: Res<Snapping>,
+    targets: Res<SnapTargets>,
+    mut mouse: ResMut<Mouse>,
+) {
+    mouse.snapped = snapping.snap(mouse.position, &targets);
+}
+
+pub fn draw_grid(snapping: Res<Snapping>, world_bounds: Res<WorldBounds>, mut gizmos: Gizmos) {
+    if !snapping.enabled || snapping.grid <= 0. {
+        return;
+    }
+    // An even number of cells keeps the lines on multiples of the grid size.
+    let cells = (Vec2::new(world_bounds.width, world_bounds.height) / snapping.grid / 2.)
+        .ceil()
+        .min(Vec2::splat(MAX_GRID_LINES / 2.))
+        * 2.;
+    gizmos.grid_2d(
+        Vec2::ZERO,
+        cells.as_uvec2(),
+        Vec2::splat(snapping.grid),
+        Color::srgba(1.0, 1.0, 1.0, 0.05),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moved_solids_snap_their_edges() {
+        let snapping = Snapping {
+            enabled: true,
+            ..default()
+        };
+        let targets = SnapTargets {
+            xs: vec![100.],
+            ys: vec![-40.],
+        };
+        // The left edge is 3 px right of the target and the bottom edge 5 px below its target.
+        let snapped = snapping.snap_moved(Vec2::new(153., -15.), Vec2::new(50., 20.), &targets);
+        assert_eq!(snapped, Vec2::new(150., -20.));
+        // Too far from any edge, so the center goes to the grid.
+        let snapped = snapping.snap_moved(Vec2::new(247., 131.), Vec2::new(50., 20.), &targets);
+        assert_eq!(snapped, Vec2::new(240., 140.));
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::balls::Ball;
+
+/// How often a sample is added to the history.
+const SAMPLE_INTERVAL: f32 = 0.25;
+/// Samples kept for the graphs, half a minute's worth.
+pub const HISTORY_LENGTH: usize = 120;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StatsSample {
+    pub balls: usize,
+    pub spawned_per_second: f32,
+    pub despawned_per_second: f32,
+    /// Milliseconds spent stepping rapier per frame, averaged over the sample.
+    pub step_time: f32,
+    pub contact_pairs: usize,
+    pub fps: f32,
+}
+
+/// Live simulation numbers with a rolling history for the statistics window.
+#[derive(Resource, Debug, Default)]
+pub struct SimulationStats {
+    pub history: VecDeque<StatsSample>,
+    spawned: usize,
+    despawned: usize,
+    step_time: Duration,
+    frames: u32,
+    elapsed: f32,
+}
+
+impl SimulationStats {
+    pub fn latest(&self) -> StatsSample {
+        self.history.back().copied().unwrap_or_default()
+    }
+}
+
+/// When the current physics step started.
+#[derive(Resource, Debug, Default)]
+pub struct StepTimer(Option<Instant>);
+
+pub fn start_step_timer(mut step_timer: ResMut<StepTimer>) {
+    step_timer.0 = Some(Instant::now());
+}
+
+pub fn stop_step_timer(mut step_timer: ResMut<StepTimer>, mut stats: ResMut<SimulationStats>) {
+    if let Some(start) = step_timer.0.take() {
+        stats.step_time += start.elapsed();
+    }
+}
+
+/// Counts spawned and despawned balls every frame and adds a sample to the history a few times a
+/// second. Uses real time so the numbers keep updating while the simulation is paused.
+pub fn collect_stats(
+    mut stats: ResMut<SimulationStats>,
+    time: Res<Time<Real>>,
+    diagnostics: Res<DiagnosticsStore>,
+    rapier_context: ReadDefaultRapierContext,
+    balls: Query<(), With<Ball>>,
+    added_balls: Query<(), Added<Ball>>,
+    mut removed_balls: RemovedComponents<Ball>,
+) {
+    stats.spawned += added_balls.iter().count();
+    stats.despawned += removed_balls.read().count();
+    stats.frames += 1;
+    stats.elapsed += time.delta_secs();
+    if stats.elapsed < SAMPLE_INTERVAL {
+        return;
+    }
+
+    let elapsed = stats.elapsed;
+    let sample = StatsSample {
+        balls: balls.iter().count(),
+        spawned_per_second: stats.spawned as f32 / elapsed,
+        despawned_per_second: stats.despawned as f32 / elapsed,
+        step_time: stats.step_time.as_secs_f32() * 1000. / stats.frames as f32,
+        contact_pairs: rapier_context
+            .contact_pairs()
+            .filter(|pair| pair.raw.has_any_active_contact)
+            .count(),
+        fps: diagnostics
+            .get(&FrameTimeDiagnosticsPlugin::FPS)
+            .and_then(|fps| fps.smoothed())
+            .unwrap_or_default() as f32,
+    };
+    if stats.history.len() == HISTORY_LENGTH {
+        stats.history.pop_front();
+    }
+    stats.history.push_back(sample);
+    stats.spawned = 0;
+    stats.despawned = 0;
+    stats.step_time = Duration::ZERO;
+    stats.frames = 0;
+    stats.elapsed = 0.;
+}
@@ -0,0 +1,140 @@
+use bevy::asset::RenderAssetUsages;
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::perlin::{create_perlin_image, Perlin};
+use crate::Solid;
+
+pub const TERRAIN_COLOR: Color = Color::srgb(0.45, 0.35, 0.2);
+/// Heights sampled across the width of a terrain.
+const SAMPLES: usize = 128;
+/// Noise cells across the texture of a terrain.
+const TEXTURE_SCALE: f32 = 8.;
+
+/// Rolling ground generated from Perlin noise. The surface runs across the solid's width and
+/// everything below it is solid.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TerrainSettings {
+    pub seed: u32,
+    pub octaves: u32,
+    /// How much of the solid's height the hills span, from 0 to 1.
+    pub amplitude: f32,
+    /// Hills across the solid's width.
+    pub frequency: f32,
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        TerrainSettings {
+            seed: 0,
+            octaves: 4,
+            amplitude: 0.8,
+            frequency: 3.,
+        }
+    }
+}
+
+impl TerrainSettings {
+    /// Surface heights in the unit square, from the left edge to the right edge.
+    pub fn heights(&self) -> Vec<f32> {
+        let perlin = Perlin::new(self.seed);
+        (0..SAMPLES)
+            .map(|i| {
+                let x = i as f32 / (SAMPLES - 1) as f32 * self.frequency;
+                let noise = perlin.fractal(Vec2::new(x, 0.5), self.octaves);
+                (noise * self.amplitude * 0.5).clamp(-0.5, 0.5)
+            })
+            .collect()
+    }
+
+    pub fn collider(&self) -> Collider {
+        Collider::heightfield(self.heights(), Vec2::ONE)
+    }
+}
+
+/// Surface heights of a terrain, kept for hovering since rapier treats heightfields as hollow.
+#[derive(Component, Debug)]
+pub struct TerrainHeights(Vec<f32>);
+
+impl TerrainHeights {
+    /// Whether a point in the solid's unit square is on or below the surface.
+    pub fn contains(&self, local: Vec2) -> bool {
+        if local.x.abs() > 0.5 || local.y < -0.5 || self.0.len() < 2 {
+            return false;
+        }
+        let position = (local.x + 0.5) * (self.0.len() - 1) as f32;
+        let index = (position as usize).min(self.0.len() - 2);
+        let height = self.0[index].lerp(self.0[index + 1], position - index as f32);
+        local.y <= height
+    }
+}
+
+/// Regenerates the mesh, texture and collider of terrains whose settings changed.
+pub fn sync_terrain(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    query: Query<
+        (
+            Entity,
+            &Solid,
+            Option<&MeshMaterial2d<ColorMaterial>>,
+            Has<RigidBody>,
+        ),
+        Changed<Solid>,
+    >,
+) {
+    for (entity, solid, material, has_body) in &query {
+        let Solid::Terrain(settings) = solid else {
+            continue;
+        };
+        let heights = settings.heights();
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.insert(Mesh2d(meshes.add(terrain_mesh(&heights))));
+        if has_body {
+            entity_commands.insert(Collider::heightfield(heights.clone(), Vec2::ONE));
+        }
+        entity_commands.insert(TerrainHeights(heights));
+
+        if let Some(material) = material.and_then(|material| materials.get_mut(material)) {
+            let perlin = Perlin::new(settings.seed);
+            let image = create_perlin_image(&perlin, TEXTURE_SCALE, settings.octaves, Color::WHITE);
+            material.texture = Some(images.add(image));
+        }
+    }
+}
+
+/// A column from the bottom of the unit square up to the surface for each sample.
+fn terrain_mesh(heights: &[f32]) -> Mesh {
+    let step = 1. / (heights.len() - 1) as f32;
+    let positions: Vec<[f32; 3]> = heights
+        .iter()
+        .enumerate()
+        .flat_map(|(i, height)| {
+            let x = i as f32 * step - 0.5;
+            [[x, *height, 0.], [x, -0.5, 0.]]
+        })
+        .collect();
+    let uvs: Vec<[f32; 2]> = positions
+        .iter()
+        .map(|position| [position[0] + 0.5, 0.5 - position[1]])
+        .collect();
+    let indices: Vec<u32> = (0..heights.len() as u32 - 1)
+        .flat_map(|i| {
+            let (top, bottom) = (i * 2, i * 2 + 1);
+            [top, bottom, bottom + 2, top, bottom + 2, top + 2]
+        })
+        .collect();
+
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0., 0., 1.]; positions.len()])
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+    .with_inserted_indices(Indices::U32(indices))
+}
@@ -1,21 +1,70 @@
-use bevy::asset::Assets;
+use bevy::asset::{Assets, Handle, RenderAssetUsages};
 use bevy::color::palettes::css::*;
-use bevy::color::{ColorToComponents, Srgba};
-use bevy::prelude::{Commands, Mesh, Mesh2d, Rectangle, ResMut, Resource};
+use bevy::color::Srgba;
+use bevy::image::BevyDefault;
+use bevy::math::Vec2;
+use bevy::prelude::{Commands, Component, Image, Mesh, Mesh2d, ResMut, Resource};
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::utils::HashMap;
 use rand::random;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
 
-pub fn generate_textures(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
-    let meshes = VERTEX_COLORS
-        .map(|colors| {
-            let vertex_colors: Vec<[f32; 4]> =
-                colors.map(|color| Srgba::to_f32_array(color)).to_vec();
-            let mut mesh = Mesh::from(Rectangle::default());
-            mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, vertex_colors);
-            let handle = Mesh2d(meshes.add(mesh));
-            handle
+use crate::balls::ParticleShape;
+use crate::shapes::SolidShape;
+
+const PARTICLE_TEXTURE_SIZE: u32 = 32;
+
+pub fn generate_textures(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let meshes = SolidShape::all()
+        .map(|shape| {
+            let variants = VERTEX_COLORS
+                .map(|colors| Mesh2d(meshes.add(shape.mesh(colors))))
+                .to_vec();
+            (shape, variants)
         })
-        .to_vec();
+        .collect();
     commands.insert_resource(Meshes { meshes });
+
+    let particle_images = ParticleShape::iter()
+        .map(|shape| (shape, images.add(create_particle_image(shape))))
+        .collect();
+    commands.insert_resource(ParticleImages {
+        images: particle_images,
+    });
+}
+
+/// White mask of a particle shape, tinted by the sprite color.
+fn create_particle_image(shape: ParticleShape) -> Image {
+    let extents = shape.extents();
+    let width = (PARTICLE_TEXTURE_SIZE as f32 * extents.x / 2.) as u32;
+    let height = (PARTICLE_TEXTURE_SIZE as f32 * extents.y / 2.) as u32;
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let point = Vec2::new(
+                ((x as f32 + 0.5) / width as f32 - 0.5) * extents.x,
+                (0.5 - (y as f32 + 0.5) / height as f32) * extents.y,
+            );
+            let alpha = if shape.contains(point) { 255 } else { 0 };
+            pixels.extend_from_slice(&[255, 255, 255, alpha]);
+        }
+    }
+    Image::new(
+        Extent3d {
+            width,
+            height,
+            ..Default::default()
+        },
+        TextureDimension::D2,
+        pixels,
+        TextureFormat::bevy_default(),
+        RenderAssetUsages::RENDER_WORLD,
+    )
 }
 
 const VERTEX_COLORS: [[Srgba; 4]; 4] = [
@@ -27,12 +76,35 @@ const VERTEX_COLORS: [[Srgba; 4]; 4] = [
 
 #[derive(Resource, Debug)]
 pub struct Meshes {
-    meshes: Vec<Mesh2d>,
+    meshes: HashMap<SolidShape, Vec<Mesh2d>>,
 }
 
+/// Index into the colors of [`Meshes`], kept on solids so the same mesh can be restored from a scene file.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MeshVariant(pub usize);
+
 impl Meshes {
-    pub(crate) fn get_random(&self) -> Mesh2d {
-        let index = random::<usize>() % self.meshes.len();
-        self.meshes[index].clone()
+    pub(crate) fn get_random(&self, shape: SolidShape) -> (MeshVariant, Mesh2d) {
+        let variant = MeshVariant(random::<usize>() % VERTEX_COLORS.len());
+        (variant, self.get(shape, variant))
+    }
+
+    pub(crate) fn get(&self, shape: SolidShape, variant: MeshVariant) -> Mesh2d {
+        let variants = self
+            .meshes
+            .get(&shape)
+            .unwrap_or(&self.meshes[&SolidShape::Rectangle]);
+        variants[variant.0 % variants.len()].clone()
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct ParticleImages {
+    images: HashMap<ParticleShape, Handle<Image>>,
+}
+
+impl ParticleImages {
+    pub(crate) fn get(&self, shape: ParticleShape) -> Handle<Image> {
+        self.images.get(&shape).cloned().unwrap_or_default()
     }
 }
@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+use bevy_rapier2d::plugin::TimestepMode;
+use bevy_rapier2d::prelude::*;
+
+/// Length of a single frame step, and of the physics steps at normal speed.
+const STEP_DT: f32 = 1. / 60.;
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct TimeControl {
+    pub paused: bool,
+    /// How fast simulated time runs compared to real time.
+    pub time_scale: f32,
+    /// Advance a single frame while paused.
+    pub step_requested: bool,
+}
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        TimeControl {
+            paused: false,
+            time_scale: 1.,
+            step_requested: false,
+        }
+    }
+}
+
+/// Space toggles pause when it was not used to pan, period steps a single frame.
+pub fn handle_time_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    mut time_control: ResMut<TimeControl>,
+    mut panned: Local<bool>,
+    mut egui_contexts: EguiContexts,
+) {
+    if egui_contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    if keyboard_input.pressed(KeyCode::Space) && mouse_input.pressed(MouseButton::Left) {
+        *panned = true;
+    }
+    if keyboard_input.just_released(KeyCode::Space) {
+        if !*panned {
+            time_control.paused = !time_control.paused;
+        }
+        *panned = false;
+    }
+    if keyboard_input.just_pressed(KeyCode::Period) {
+        if time_control.paused {
+            time_control.step_requested = true;
+        } else {
+            time_control.paused = true;
+        }
+    }
+}
+
+/// Pauses and scales virtual time, which drives the emitters and animations, and keeps rapier
+/// in step with it. A frame step advances virtual time by hand for the current frame.
+pub fn apply_time_control(
+    mut time_control: ResMut<TimeControl>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut time: ResMut<Time>,
+    mut timestep_mode: ResMut<TimestepMode>,
+    mut rapier_configurations: Query<&mut RapierConfiguration>,
+) {
+    if time_control.is_changed() {
+        let time_scale = time_control.time_scale.max(0.01);
+        virtual_time.set_relative_speed(time_scale);
+        // Faster than real time takes bigger steps, split into substeps to stay stable.
+        *timestep_mode = TimestepMode::Variable {
+            max_dt: STEP_DT * time_scale.max(1.),
+            time_scale: 1.,
+            substeps: time_scale.ceil().max(1.) as usize,
+        };
+    }
+
+    let step = time_control.paused && time_control.step_requested;
+    if time_control.step_requested {
+        time_control.step_requested = false;
+    }
+    if time_control.paused {
+        virtual_time.pause();
+    } else {
+        virtual_time.unpause();
+    }
+    if step {
+        virtual_time.advance_by(Duration::from_secs_f32(STEP_DT));
+        *time = virtual_time.as_generic();
+    }
+
+    let active = !time_control.paused || step;
+    for mut configuration in &mut rapier_configurations {
+        if configuration.physics_pipeline_active != active {
+            configuration.physics_pipeline_active = active;
+        }
+    }
+}
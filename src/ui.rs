@@ -1,14 +1,58 @@
-use bevy::prelude::{EventWriter, Res};
-use bevy_egui::egui::Window;
+use bevy::color::{ColorToComponents, Srgba};
+use bevy::prelude::{
+    Color, Commands, DetectChangesMut, Entity, EventWriter, Query, Res, ResMut, Transform, Vec2,
+    With,
+};
+use bevy_egui::egui::{
+    pos2, vec2, Button, CollapsingHeader, Color32, ComboBox, DragValue, ScrollArea, Sense, Shape,
+    Slider, Stroke, Ui, Window,
+};
 use bevy_egui::EguiContexts;
 use strum::IntoEnumIterator;
 
-use crate::{Mode, Tool, ToolEvent};
+use crate::balls::{ParticleShape, ParticleSpec, SizeDistribution};
+use crate::bodies::{BodyType, BoxDefaults};
+use crate::camera::FitSceneEvent;
+use crate::counters::{CounterSettings, CounterState};
+use crate::emitters::EmitterSettings;
+use crate::force_fields::FieldKind;
+use crate::history::{History, HistoryEntry, HistoryEvent};
+use crate::joints::{despawn_joint, Joint, JointKind, JointTool, JointType, Motor, WorldAnchor};
+use crate::materials::{MaterialLibrary, NoiseMaterial};
+use crate::motion::Motion;
+use crate::scene::{SceneEvent, SceneFile, SceneJoint};
+use crate::selection::Selected;
+use crate::shapes::POLYGON_SIDES;
+use crate::snapping::Snapping;
+use crate::stats::{SimulationStats, StatsSample, HISTORY_LENGTH};
+use crate::terrain::TerrainSettings;
+use crate::time_control::TimeControl;
+use crate::world::{EdgeBehaviour, WorldBounds};
+use crate::{Clipboard, Mode, Solid, Tool, ToolEvent};
+
+/// Panels edit a copy of a resource or component and write it back through this, so change
+/// detection only fires on actual edits. Walls, textures, meshes, colliders and joints are
+/// rebuilt on change, so writing back every frame a panel is open would rebuild them constantly.
+fn set_if_changed<T: PartialEq>(target: &mut impl DetectChangesMut<Inner = T>, value: T) {
+    target.set_if_neq(value);
+}
 
 pub fn update_ui(
     mut egui_contexts: EguiContexts,
     mode: Res<Mode>,
     mut event_sender: EventWriter<ToolEvent>,
+    mut scene_event_sender: EventWriter<SceneEvent>,
+    mut scene_file: ResMut<SceneFile>,
+    mut history_event_sender: EventWriter<HistoryEvent>,
+    mut fit_scene_sender: EventWriter<FitSceneEvent>,
+    mut history: ResMut<History>,
+    mut clipboard: ResMut<Clipboard>,
+    mut world_bounds: ResMut<WorldBounds>,
+    mut snapping: ResMut<Snapping>,
+    mut box_defaults: ResMut<BoxDefaults>,
+    mut material_library: ResMut<MaterialLibrary>,
+    mut time_control: ResMut<TimeControl>,
+    mut solids: Query<(Entity, &mut Solid)>,
 ) {
     let ctx = egui_contexts.ctx_mut();
 
@@ -26,5 +70,693 @@ pub fn update_ui(
         for tool in Tool::iter() {
             add_button(tool.label(), tool);
         }
+        CollapsingHeader::new("New bodies").show(ui, |ui| {
+            let defaults = &mut *box_defaults;
+            body_ui(ui, &mut defaults.body, &mut defaults.density);
+            ui.add(Slider::new(&mut defaults.polygon_sides, POLYGON_SIDES).text("Polygon sides"));
+        });
+
+        ui.separator();
+        ui.label("Simulation");
+        ui.horizontal(|ui| {
+            let label = if time_control.paused {
+                "Resume (Space)"
+            } else {
+                "Pause (Space)"
+            };
+            if ui.button(label).clicked() {
+                time_control.paused = !time_control.paused;
+            }
+            if ui
+                .add_enabled(time_control.paused, Button::new("Step (.)"))
+                .clicked()
+            {
+                time_control.step_requested = true;
+            }
+        });
+        let mut time_scale = time_control.time_scale;
+        ui.add(
+            Slider::new(&mut time_scale, 0.1..=4.0)
+                .logarithmic(true)
+                .text("Time scale"),
+        );
+        if time_scale != time_control.time_scale {
+            time_control.time_scale = time_scale;
+        }
+
+        ui.separator();
+        ui.label("Scene");
+        ui.text_edit_singleline(&mut scene_file.0);
+        if ui.button("Fit scene (Home)").clicked() {
+            fit_scene_sender.send(FitSceneEvent);
+        }
+        ui.add_enabled_ui(*mode == Mode::Default, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Save (Ctrl+S)").clicked() {
+                    scene_event_sender.send(SceneEvent::Save);
+                }
+                if ui.button("Load (Ctrl+O)").clicked() {
+                    scene_event_sender.send(SceneEvent::Load);
+                }
+            });
+        });
+
+        ui.separator();
+        ui.label("History");
+        ui.add_enabled_ui(*mode == Mode::Default, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Undo (Ctrl+Z)").clicked() {
+                    history_event_sender.send(HistoryEvent::Undo);
+                }
+                if ui.button("Redo (Ctrl+Shift+Z)").clicked() {
+                    history_event_sender.send(HistoryEvent::Redo);
+                }
+            });
+        });
+        ScrollArea::vertical().max_height(150.).show(ui, |ui| {
+            for step in history.undo_steps() {
+                ui.label(step.label());
+            }
+            for step in history.redo_steps().iter().rev() {
+                ui.weak(step.label());
+            }
+        });
+
+        ui.separator();
+        CollapsingHeader::new("World").show(ui, |ui| {
+            let mut bounds = *world_bounds;
+            world_bounds_ui(ui, &mut bounds);
+            set_if_changed(&mut world_bounds, bounds);
+        });
+
+        CollapsingHeader::new("Snapping").show(ui, |ui| snapping_ui(ui, &mut snapping));
+
+        CollapsingHeader::new("Materials").show(ui, |ui| {
+            let mut library = material_library.clone();
+            if let Some(removed) = material_library_ui(ui, &mut library) {
+                for (_, mut solid) in &mut solids {
+                    let mut edited = solid.clone();
+                    edited.remove_material(removed);
+                    set_if_changed(&mut solid, edited);
+                }
+                history.remove_material(removed);
+                for copied in &mut clipboard.solids {
+                    copied.solid.remove_material(removed);
+                }
+            }
+            set_if_changed(&mut material_library, library);
+        });
+
+        CollapsingHeader::new("Emitters").show(ui, |ui| {
+            for (entity, mut solid) in &mut solids {
+                let Solid::Emitter(settings) = &*solid else {
+                    continue;
+                };
+                let mut edited = settings.clone();
+                CollapsingHeader::new(format!("Emitter {entity}"))
+                    .id_salt(entity)
+                    .show(ui, |ui| emitter_ui(ui, &mut edited));
+                set_if_changed(&mut solid, Solid::Emitter(edited));
+            }
+        });
+    });
+}
+
+pub fn update_inspector(
+    mut egui_contexts: EguiContexts,
+    material_library: Res<MaterialLibrary>,
+    mut query: Query<(Entity, &mut Solid), With<Selected>>,
+) {
+    if query.is_empty() {
+        return;
+    }
+    let ctx = egui_contexts.ctx_mut();
+
+    Window::new("Inspector").show(ctx, |ui| {
+        for (entity, mut solid) in &mut query {
+            ui.heading(format!("{} {entity}", solid.label()));
+            let mut edited = solid.clone();
+            match &mut edited {
+                Solid::Box {
+                    body,
+                    density,
+                    motion,
+                    material,
+                    ..
+                } => {
+                    body_ui(ui, body, density);
+                    motion_ui(ui, motion);
+                    material_ui(ui, material, &material_library);
+                }
+                Solid::ForceField {
+                    direction,
+                    magnitude,
+                    falloff,
+                    kind,
+                } => force_field_ui(ui, direction, magnitude, falloff, kind),
+                Solid::Emitter(settings) => emitter_ui(ui, settings),
+                Solid::Counter(settings) => counter_ui(ui, settings),
+                Solid::Terrain(settings) => terrain_ui(ui, settings),
+                Solid::Polyline { points } => {
+                    ui.label(format!("{} points", points.len()));
+                }
+            }
+            set_if_changed(&mut solid, edited);
+        }
+    });
+}
+
+/// Lists the joints for editing, along with the joint type used by the joint tool.
+pub fn update_joint_window(
+    mut commands: Commands,
+    mut egui_contexts: EguiContexts,
+    mode: Res<Mode>,
+    mut joint_tool: ResMut<JointTool>,
+    mut history: ResMut<History>,
+    mut joints: Query<(Entity, &mut Joint)>,
+    anchors: Query<&Transform, With<WorldAnchor>>,
+) {
+    if *mode != Mode::Joint && joints.is_empty() {
+        return;
+    }
+    let ctx = egui_contexts.ctx_mut();
+
+    Window::new("Joints").show(ctx, |ui| {
+        if *mode == Mode::Joint {
+            ui.label("Click two points on boxes or empty space to join them.");
+            ui.horizontal_wrapped(|ui| {
+                for joint_type in JointType::iter() {
+                    ui.radio_value(&mut joint_tool.joint_type, joint_type, joint_type.label());
+                }
+            });
+            ui.separator();
+        }
+        for (entity, mut joint) in &mut joints {
+            CollapsingHeader::new(format!("{} {entity}", joint.kind.joint_type().label()))
+                .id_salt(entity)
+                .show(ui, |ui| {
+                    let mut edited = *joint;
+                    joint_kind_ui(ui, &mut edited.kind);
+                    set_if_changed(&mut joint, edited);
+                    if ui.button("Delete").clicked() {
+                        history.push(HistoryEntry::Joint {
+                            joint: SceneJoint::from_joint(&joint, &anchors),
+                            created: false,
+                        });
+                        despawn_joint(&mut commands, entity, &joint, &anchors);
+                    }
+                });
+        }
+    });
+}
+
+/// Lists the counters with their counts, so funnels and sorters can be compared at a glance.
+pub fn update_counter_window(
+    mut egui_contexts: EguiContexts,
+    mut counters: Query<(Entity, &mut Solid, &mut CounterState)>,
+) {
+    if counters.is_empty() {
+        return;
+    }
+    let ctx = egui_contexts.ctx_mut();
+
+    Window::new("Counters").show(ctx, |ui| {
+        let mut total = 0;
+        for (entity, mut solid, mut state) in &mut counters {
+            let Solid::Counter(settings) = solid.clone() else {
+                continue;
+            };
+            total += state.count;
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "Counter {entity}: {} ({:.1}/s)",
+                    state.count,
+                    state.rate()
+                ));
+                let mut edited = settings.clone();
+                counter_ui(ui, &mut edited);
+                set_if_changed(&mut solid, Solid::Counter(edited));
+                if ui.small_button("Reset").clicked() {
+                    state.reset();
+                }
+            });
+        }
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label(format!("Total: {total}"));
+            if ui.button("Reset all").clicked() {
+                for (_, _, mut state) in &mut counters {
+                    state.reset();
+                }
+            }
+        });
+    });
+}
+
+fn counter_ui(ui: &mut Ui, settings: &mut CounterSettings) {
+    ui.checkbox(&mut settings.consume, "Consume balls");
+}
+
+fn joint_kind_ui(ui: &mut Ui, kind: &mut JointKind) {
+    match kind {
+        JointKind::Revolute { limits, motor } => {
+            ui.checkbox(&mut limits.enabled, "Limit angle");
+            ui.add_enabled_ui(limits.enabled, |ui| {
+                ui.horizontal(|ui| {
+                    ui.drag_angle(&mut limits.min);
+                    ui.drag_angle(&mut limits.max);
+                });
+            });
+            motor_ui(ui, motor, "Speed (rad/s)", 20.);
+        }
+        JointKind::Prismatic { limits, motor, .. } => {
+            ui.checkbox(&mut limits.enabled, "Limit distance");
+            ui.add_enabled_ui(limits.enabled, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(DragValue::new(&mut limits.min).prefix("Min "));
+                    ui.add(DragValue::new(&mut limits.max).prefix("Max "));
+                });
+            });
+            motor_ui(ui, motor, "Speed (px/s)", 500.);
+        }
+        JointKind::Fixed { .. } => {}
+        JointKind::Rope { length } => {
+            ui.add(
+                DragValue::new(length)
+                    .range(0.0..=10000.0)
+                    .prefix("Length "),
+            );
+        }
+        JointKind::Spring {
+            rest_length,
+            stiffness,
+            damping,
+        } => {
+            ui.add(
+                DragValue::new(rest_length)
+                    .range(0.0..=10000.0)
+                    .prefix("Rest length "),
+            );
+            ui.add(
+                Slider::new(stiffness, 0.0..=1e7)
+                    .logarithmic(true)
+                    .text("Stiffness"),
+            );
+            ui.add(
+                Slider::new(damping, 0.0..=1e5)
+                    .logarithmic(true)
+                    .text("Damping"),
+            );
+        }
+    }
+}
+
+fn motor_ui(ui: &mut Ui, motor: &mut Motor, speed_label: &str, max_speed: f32) {
+    ui.checkbox(&mut motor.enabled, "Motor");
+    ui.add_enabled_ui(motor.enabled, |ui| {
+        ui.add(Slider::new(&mut motor.target_velocity, -max_speed..=max_speed).text(speed_label));
+        ui.add(
+            Slider::new(&mut motor.max_force, 0.0..=1e10)
+                .logarithmic(true)
+                .text("Max force"),
+        );
+    });
+}
+
+fn body_ui(ui: &mut Ui, body: &mut BodyType, density: &mut f32) {
+    ui.horizontal(|ui| {
+        for option in BodyType::iter() {
+            ui.radio_value(body, option, option.label());
+        }
+    });
+    ui.add_enabled(
+        *body == BodyType::Dynamic,
+        Slider::new(density, 0.1..=10.0).text("Density"),
+    );
+}
+
+fn motion_ui(ui: &mut Ui, motion: &mut Motion) {
+    ComboBox::from_label("Motion")
+        .selected_text(motion.label())
+        .show_ui(ui, |ui| {
+            for option in [
+                Motion::None,
+                Motion::Spin {
+                    angular_velocity: 1.,
+                },
+                Motion::Shuttle {
+                    offset: Vec2::new(200., 0.),
+                    speed: 100.,
+                },
+                Motion::Oscillate {
+                    amplitude: Vec2::new(0., 100.),
+                    period: 2.,
+                },
+            ] {
+                let selected = std::mem::discriminant(motion) == std::mem::discriminant(&option);
+                if ui.selectable_label(selected, option.label()).clicked() && !selected {
+                    *motion = option;
+                }
+            }
+        });
+
+    match motion {
+        Motion::None => {}
+        Motion::Spin { angular_velocity } => {
+            ui.add(Slider::new(angular_velocity, -10.0..=10.0).text("Angular velocity (rad/s)"));
+        }
+        Motion::Shuttle { offset, speed } => {
+            vec2_ui(ui, "Offset", offset);
+            ui.add(Slider::new(speed, 0.0..=1000.0).text("Speed (px/s)"));
+        }
+        Motion::Oscillate { amplitude, period } => {
+            vec2_ui(ui, "Amplitude", amplitude);
+            ui.add(Slider::new(period, 0.1..=20.0).text("Period (s)"));
+        }
+    }
+    if *motion != Motion::None {
+        ui.weak("Motion applies to kinematic boxes.");
+    }
+}
+
+fn vec2_ui(ui: &mut Ui, label: &str, value: &mut Vec2) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        ui.add(DragValue::new(&mut value.x).prefix("x "));
+        ui.add(DragValue::new(&mut value.y).prefix("y "));
     });
 }
+
+fn world_bounds_ui(ui: &mut Ui, bounds: &mut WorldBounds) {
+    ui.add(
+        DragValue::new(&mut bounds.width)
+            .range(100.0..=20000.0)
+            .prefix("Width "),
+    );
+    ui.add(
+        DragValue::new(&mut bounds.height)
+            .range(100.0..=20000.0)
+            .prefix("Height "),
+    );
+    let edges = &mut bounds.edges;
+    for (label, edge) in [
+        ("Left", &mut edges.left),
+        ("Right", &mut edges.right),
+        ("Top", &mut edges.top),
+        ("Bottom", &mut edges.bottom),
+    ] {
+        ComboBox::from_label(label)
+            .selected_text(edge.label())
+            .show_ui(ui, |ui| {
+                for behaviour in EdgeBehaviour::iter() {
+                    ui.selectable_value(edge, behaviour, behaviour.label());
+                }
+            });
+    }
+}
+
+fn snapping_ui(ui: &mut Ui, snapping: &mut Snapping) {
+    ui.checkbox(&mut snapping.enabled, "Snap to grid");
+    ui.add(
+        DragValue::new(&mut snapping.grid)
+            .range(1.0..=500.0)
+            .prefix("Grid "),
+    );
+    ui.checkbox(&mut snapping.snap_to_edges, "Snap to edges of other solids");
+    ui.add(Slider::new(&mut snapping.edge_distance, 0.0..=50.0).text("Edge distance"));
+    ui.add(Slider::new(&mut snapping.angle_step, 1.0..=90.0).text("Angle step with Shift (°)"));
+}
+
+fn force_field_ui(
+    ui: &mut Ui,
+    direction: &mut f32,
+    magnitude: &mut f32,
+    falloff: &mut f32,
+    kind: &mut FieldKind,
+) {
+    ComboBox::from_label("Kind")
+        .selected_text(kind.label())
+        .show_ui(ui, |ui| {
+            for option in [
+                FieldKind::Linear,
+                FieldKind::Radial { strength: 0.5 },
+                FieldKind::Vortex { strength: 0.5 },
+                FieldKind::Drag { damping: 2.0 },
+                FieldKind::Gradient,
+                FieldKind::Turbulence {
+                    strength: 0.5,
+                    scale: 200.,
+                    speed: 0.5,
+                },
+            ] {
+                let selected = std::mem::discriminant(kind) == std::mem::discriminant(&option);
+                if ui.selectable_label(selected, option.label()).clicked() && !selected {
+                    *kind = option;
+                }
+            }
+        });
+
+    match kind {
+        FieldKind::Linear | FieldKind::Gradient => {}
+        FieldKind::Radial { strength } => {
+            ui.add(Slider::new(strength, -5.0..=5.0).text("Strength (negative repels)"));
+        }
+        FieldKind::Vortex { strength } => {
+            ui.add(Slider::new(strength, -5.0..=5.0).text("Strength (negative is clockwise)"));
+        }
+        FieldKind::Drag { damping } => {
+            ui.add(Slider::new(damping, 0.0..=20.0).text("Damping (1/s)"));
+        }
+        FieldKind::Turbulence {
+            strength,
+            scale,
+            speed,
+        } => {
+            ui.add(Slider::new(strength, 0.0..=5.0).text("Strength"));
+            ui.add(
+                Slider::new(scale, 10.0..=2000.0)
+                    .logarithmic(true)
+                    .text("Scale (px)"),
+            );
+            ui.add(Slider::new(speed, 0.0..=5.0).text("Scroll speed"));
+        }
+    }
+    ui.add(Slider::new(falloff, 0.0..=1.0).text("Falloff"));
+
+    if !matches!(kind, FieldKind::Linear | FieldKind::Gradient) {
+        return;
+    }
+    ui.add(Slider::new(magnitude, 0.0..=5.0).text("Magnitude"));
+    let mut angle = direction.to_degrees();
+    if ui
+        .add(Slider::new(&mut angle, -180.0..=180.0).text("Direction (°)"))
+        .changed()
+    {
+        *direction = angle.to_radians();
+    }
+}
+
+fn material_ui(ui: &mut Ui, material: &mut Option<usize>, library: &MaterialLibrary) {
+    ComboBox::from_label("Material")
+        .selected_text(library.name(*material))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(material, None, "None");
+            for (index, option) in library.materials.iter().enumerate() {
+                ui.selectable_value(material, Some(index), &option.name);
+            }
+        });
+}
+
+/// Returns the index of the material that was removed, if any.
+fn material_library_ui(ui: &mut Ui, library: &mut MaterialLibrary) -> Option<usize> {
+    let mut removed = None;
+    for (index, material) in library.materials.iter_mut().enumerate() {
+        CollapsingHeader::new(&material.name)
+            .id_salt(index)
+            .show(ui, |ui| {
+                ui.text_edit_singleline(&mut material.name);
+                ui.horizontal(|ui| {
+                    ui.add(DragValue::new(&mut material.seed).prefix("Seed: "));
+                    if ui.button("Randomize").clicked() {
+                        material.seed = rand::random();
+                    }
+                });
+                ui.add(Slider::new(&mut material.scale, 1.0..=32.0).text("Scale"));
+                ui.add(Slider::new(&mut material.octaves, 1..=8).text("Octaves"));
+                ui.horizontal(|ui| {
+                    let mut rgba = material.tint.to_srgba().to_f32_array();
+                    if ui.color_edit_button_rgba_unmultiplied(&mut rgba).changed() {
+                        material.tint = Color::from(Srgba::from_f32_array(rgba));
+                    }
+                    ui.label("Tint");
+                });
+                ui.add(
+                    Slider::new(&mut material.tile_size, 10.0..=1000.0)
+                        .logarithmic(true)
+                        .text("Tile size (px)"),
+                );
+                if ui.button("Remove").clicked() {
+                    removed = Some(index);
+                }
+            });
+    }
+    if let Some(index) = removed {
+        library.materials.remove(index);
+    }
+    if ui.button("Add material").clicked() {
+        library.materials.push(NoiseMaterial {
+            name: format!("Material {}", library.materials.len() + 1),
+            seed: rand::random(),
+            ..NoiseMaterial::default()
+        });
+    }
+    removed
+}
+
+fn terrain_ui(ui: &mut Ui, settings: &mut TerrainSettings) {
+    ui.horizontal(|ui| {
+        ui.add(DragValue::new(&mut settings.seed).prefix("Seed: "));
+        if ui.button("Randomize").clicked() {
+            settings.seed = rand::random();
+        }
+    });
+    ui.add(Slider::new(&mut settings.octaves, 1..=8).text("Octaves"));
+    ui.add(Slider::new(&mut settings.amplitude, 0.0..=1.0).text("Amplitude"));
+    ui.add(
+        Slider::new(&mut settings.frequency, 0.5..=32.0)
+            .logarithmic(true)
+            .text("Frequency"),
+    );
+}
+
+fn emitter_ui(ui: &mut Ui, settings: &mut EmitterSettings) {
+    ui.add(Slider::new(&mut settings.rate, 0.0..=500.0).text("Rate (balls/s)"));
+
+    let mut angle = settings.direction.to_angle().to_degrees();
+    if ui
+        .add(Slider::new(&mut angle, -180.0..=180.0).text("Direction (°)"))
+        .changed()
+    {
+        settings.direction = Vec2::from_angle(angle.to_radians());
+    }
+    ui.add(Slider::new(&mut settings.speed, 0.0..=2000.0).text("Speed"));
+
+    CollapsingHeader::new("Particles").show(ui, |ui| particle_spec_ui(ui, &mut settings.particle));
+
+    ui.add(Slider::new(&mut settings.max_alive, 0..=20000).text("Max alive"));
+
+    ui.label("Palette (empty for random colors)");
+    let mut removed = None;
+    for (index, color) in settings.palette.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            let mut rgba = color.to_srgba().to_f32_array();
+            if ui.color_edit_button_rgba_unmultiplied(&mut rgba).changed() {
+                *color = Color::from(Srgba::from_f32_array(rgba));
+            }
+            if ui.small_button("Remove").clicked() {
+                removed = Some(index);
+            }
+        });
+    }
+    if let Some(index) = removed {
+        settings.palette.remove(index);
+    }
+    if ui.button("Add color").clicked() {
+        settings.palette.push(Color::WHITE);
+    }
+}
+
+fn particle_spec_ui(ui: &mut Ui, spec: &mut ParticleSpec) {
+    ui.horizontal(|ui| {
+        for shape in ParticleShape::iter() {
+            let mut enabled = spec.shapes.contains(&shape);
+            if ui.checkbox(&mut enabled, shape.label()).changed() {
+                if enabled {
+                    spec.shapes.push(shape);
+                } else {
+                    spec.shapes.retain(|s| *s != shape);
+                }
+            }
+        }
+    });
+
+    ui.add(Slider::new(&mut spec.min_size, 0.5..=20.0).text("Min size"));
+    ui.add(Slider::new(&mut spec.max_size, 0.5..=20.0).text("Max size"));
+    spec.max_size = spec.max_size.max(spec.min_size);
+    ui.horizontal(|ui| {
+        ui.radio_value(
+            &mut spec.size_distribution,
+            SizeDistribution::Uniform,
+            "Uniform",
+        );
+        ui.radio_value(
+            &mut spec.size_distribution,
+            SizeDistribution::Normal,
+            "Normal",
+        );
+        ui.radio_value(
+            &mut spec.size_distribution,
+            SizeDistribution::Bimodal,
+            "Bimodal",
+        );
+    });
+
+    ui.add(Slider::new(&mut spec.restitution, 0.0..=1.0).text("Restitution"));
+    ui.add(Slider::new(&mut spec.friction, 0.0..=2.0).text("Friction"));
+    ui.add(Slider::new(&mut spec.density, 0.1..=10.0).text("Density"));
+}
+
+pub fn update_stats_window(mut egui_contexts: EguiContexts, stats: Res<SimulationStats>) {
+    let ctx = egui_contexts.ctx_mut();
+    let latest = stats.latest();
+    Window::new("Statistics")
+        .default_open(false)
+        .show(ctx, |ui| {
+            let mut row = |label: String, value: fn(&StatsSample) -> f32| {
+                ui.label(label);
+                stats_graph(ui, stats.history.iter().map(value));
+            };
+            row(format!("FPS: {:.0}", latest.fps), |sample| sample.fps);
+            row(format!("Balls: {}", latest.balls), |sample| {
+                sample.balls as f32
+            });
+            row(
+                format!("Spawned: {:.1}/s", latest.spawned_per_second),
+                |sample| sample.spawned_per_second,
+            );
+            row(
+                format!("Despawned: {:.1}/s", latest.despawned_per_second),
+                |sample| sample.despawned_per_second,
+            );
+            row(format!("Step time: {:.2} ms", latest.step_time), |sample| {
+                sample.step_time
+            });
+            row(
+                format!("Contact pairs: {}", latest.contact_pairs),
+                |sample| sample.contact_pairs as f32,
+            );
+        });
+}
+
+/// Line graph of `values` scaled to the largest one, oldest on the left.
+fn stats_graph(ui: &mut Ui, values: impl ExactSizeIterator<Item = f32> + Clone) {
+    let (response, painter) = ui.allocate_painter(vec2(200., 32.), Sense::hover());
+    let rect = response.rect;
+    painter.rect_filled(rect, 2., Color32::from_black_alpha(64));
+    let max = values.clone().fold(0., f32::max);
+    if max <= 0. {
+        return;
+    }
+    let step = rect.width() / (HISTORY_LENGTH - 1) as f32;
+    let offset = HISTORY_LENGTH - values.len();
+    let points = values
+        .enumerate()
+        .map(|(i, value)| {
+            pos2(
+                rect.left() + (i + offset) as f32 * step,
+                rect.bottom() - value / max * rect.height(),
+            )
+        })
+        .collect();
+    painter.add(Shape::line(points, Stroke::new(1.5, Color32::LIGHT_GREEN)));
+}
@@ -0,0 +1,171 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+
+use crate::balls::Ball;
+
+const WALL_THICKNESS: f32 = 20.;
+
+/// What happens to balls that cross an edge of the world.
+#[derive(EnumIter, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EdgeBehaviour {
+    /// Nothing, until the ball is a full world size away.
+    #[default]
+    Open,
+    /// A fixed wall just outside the edge.
+    Wall,
+    /// The ball reappears at the opposite edge.
+    Wrap,
+    /// The ball is despawned.
+    Kill,
+}
+
+impl EdgeBehaviour {
+    pub fn label(&self) -> &str {
+        match self {
+            EdgeBehaviour::Open => "Open",
+            EdgeBehaviour::Wall => "Wall",
+            EdgeBehaviour::Wrap => "Wrap",
+            EdgeBehaviour::Kill => "Kill",
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            EdgeBehaviour::Open => Color::srgba(1.0, 1.0, 1.0, 0.2),
+            EdgeBehaviour::Wall => Color::WHITE,
+            EdgeBehaviour::Wrap => Color::srgb(0.3, 0.6, 1.0),
+            EdgeBehaviour::Kill => Color::srgb(1.0, 0.2, 0.2),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Edges {
+    pub left: EdgeBehaviour,
+    pub right: EdgeBehaviour,
+    pub top: EdgeBehaviour,
+    pub bottom: EdgeBehaviour,
+}
+
+/// Size of the simulated world, centered on the origin. Saved with the scene, and set from the
+/// command line when running headless.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WorldBounds {
+    pub width: f32,
+    pub height: f32,
+    #[serde(default)]
+    pub edges: Edges,
+}
+
+impl Default for WorldBounds {
+    fn default() -> Self {
+        WorldBounds {
+            width: 1280.,
+            height: 720.,
+            edges: Edges::default(),
+        }
+    }
+}
+
+impl WorldBounds {
+    pub fn half_size(&self) -> Vec2 {
+        Vec2::new(self.width, self.height) / 2.
+    }
+}
+
+#[derive(Component)]
+pub struct WorldWall;
+
+/// Respawns the walls whenever the world bounds change.
+pub fn sync_world_walls(
+    mut commands: Commands,
+    world_bounds: Res<WorldBounds>,
+    walls: Query<Entity, With<WorldWall>>,
+) {
+    if !world_bounds.is_changed() {
+        return;
+    }
+    for entity in &walls {
+        commands.entity(entity).despawn();
+    }
+
+    let half = world_bounds.half_size();
+    let offset = WALL_THICKNESS / 2.;
+    let edges = world_bounds.edges;
+    let horizontal = Vec2::new(half.x + WALL_THICKNESS, offset);
+    let vertical = Vec2::new(offset, half.y + WALL_THICKNESS);
+    for (behaviour, center, half_extents) in [
+        (edges.left, Vec2::new(-half.x - offset, 0.), vertical),
+        (edges.right, Vec2::new(half.x + offset, 0.), vertical),
+        (edges.top, Vec2::new(0., half.y + offset), horizontal),
+        (edges.bottom, Vec2::new(0., -half.y - offset), horizontal),
+    ] {
+        if behaviour == EdgeBehaviour::Wall {
+            commands.spawn((
+                WorldWall,
+                RigidBody::Fixed,
+                Collider::cuboid(half_extents.x, half_extents.y),
+                Transform::from_translation(center.extend(0.)),
+            ));
+        }
+    }
+}
+
+/// Wraps or despawns balls that crossed an edge. Balls a full world size outside are despawned
+/// regardless, so open edges don't leak entities. Solids are left alone wherever they are placed.
+pub fn apply_world_edges(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Transform), With<Ball>>,
+    world_bounds: Res<WorldBounds>,
+) {
+    let half = world_bounds.half_size();
+    let size = half * 2.;
+    let edges = world_bounds.edges;
+    for (entity, mut transform) in &mut query {
+        let position = transform.translation.truncate();
+        let far_outside = position.x.abs() > size.x || position.y.abs() > size.y;
+        let crossed = [
+            (position.x < -half.x, edges.left, Vec2::X * size.x),
+            (position.x > half.x, edges.right, Vec2::NEG_X * size.x),
+            (position.y > half.y, edges.top, Vec2::NEG_Y * size.y),
+            (position.y < -half.y, edges.bottom, Vec2::Y * size.y),
+        ];
+
+        let mut kill = far_outside;
+        let mut wrap = Vec2::ZERO;
+        for (outside, behaviour, shift) in crossed {
+            match behaviour {
+                EdgeBehaviour::Kill if outside => kill = true,
+                EdgeBehaviour::Wrap if outside => wrap += shift,
+                _ => {}
+            }
+        }
+
+        if kill {
+            if let Some(mut entity_commands) = commands.get_entity(entity) {
+                entity_commands.despawn();
+            }
+        } else if wrap != Vec2::ZERO {
+            transform.translation += wrap.extend(0.);
+        }
+    }
+}
+
+pub fn draw_world_bounds(world_bounds: Res<WorldBounds>, mut gizmos: Gizmos) {
+    let half = world_bounds.half_size();
+    let edges = world_bounds.edges;
+    let top_left = Vec2::new(-half.x, half.y);
+    let top_right = half;
+    let bottom_left = -half;
+    let bottom_right = Vec2::new(half.x, -half.y);
+    for (behaviour, start, end) in [
+        (edges.left, bottom_left, top_left),
+        (edges.right, bottom_right, top_right),
+        (edges.top, top_left, top_right),
+        (edges.bottom, bottom_left, bottom_right),
+    ] {
+        gizmos.line_2d(start, end, behaviour.color());
+    }
+}